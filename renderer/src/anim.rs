@@ -0,0 +1,198 @@
+// keyframed animation clips
+// a clip is a bunch of tracks, each track moves one named joint (or scene
+// node / mesh part, anything with rotation angles) through a list of keys
+// sampling is by time in seconds so the speed doesn't depend on the frame rate
+
+use crate::skeleton::Pose;
+use crate::math::V3;
+use crate::quat::Quat;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Interp {Linear, Cubic, Ease, Slerp}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PlayMode {Once, Loop, PingPong}
+
+impl Interp {
+    pub fn parse(s: &str) -> Option<Interp> {
+        match s {
+            "linear" => Some(Interp::Linear),
+            "cubic" => Some(Interp::Cubic),
+            "ease" => Some(Interp::Ease),
+            "slerp" => Some(Interp::Slerp),
+            _ => None,
+        }
+    }
+}
+
+impl PlayMode {
+    pub fn parse(s: &str) -> Option<PlayMode> {
+        match s {
+            "once" => Some(PlayMode::Once),
+            "loop" => Some(PlayMode::Loop),
+            "pingpong" => Some(PlayMode::PingPong),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    pub time: f64,
+    pub angles: V3,
+}
+
+#[derive(Clone, Debug)]
+pub struct Track {
+    pub target: String,
+    pub keys: Vec<Keyframe>, // sorted by time
+}
+
+#[derive(Clone, Debug)]
+pub struct Clip {
+    pub tracks: Vec<Track>,
+    pub interp: Interp,
+    pub mode: PlayMode,
+}
+
+// catmull-rom through p1 and p2, p0 and p3 only bend the curve
+fn catmull_rom(p0: V3, p1: V3, p2: V3, p3: V3, t: f64) -> V3 {
+    let f = |a: f64, b: f64, c: f64, d: f64| {
+        0.5 * (2.0 * b + (c - a) * t + (2.0 * a - 5.0 * b + 4.0 * c - d) * t * t + (3.0 * b - a - 3.0 * c + d) * t * t * t)
+    };
+    V3 {x: f(p0.x, p1.x, p2.x, p3.x), y: f(p0.y, p1.y, p2.y, p3.y), z: f(p0.z, p1.z, p2.z, p3.z)}
+}
+
+impl Track {
+    pub fn sample(&self, time: f64, interp: Interp) -> V3 {
+        let keys = &self.keys;
+        if keys.is_empty() {
+            return V3::ZERO;
+        }
+        if time <= keys[0].time {
+            return keys[0].angles;
+        }
+        if time >= keys[keys.len() - 1].time {
+            return keys[keys.len() - 1].angles;
+        }
+
+        // the key we're just past, the one after it is where we're headed
+        let i = keys.iter().rposition(|k| k.time <= time).unwrap();
+        let (a, b) = (keys[i], keys[i + 1]);
+        let t = (time - a.time) / (b.time - a.time);
+
+        match interp {
+            Interp::Linear => a.angles.lerp(b.angles, t),
+            Interp::Ease => a.angles.lerp(b.angles, t * t * (3.0 - 2.0 * t)),
+            // the keys are still euler angles, only the blend goes through quaternions
+            Interp::Slerp => Quat::from_euler(a.angles).slerp(Quat::from_euler(b.angles), t).to_euler(),
+            Interp::Cubic => {
+                let before = if i > 0 {keys[i - 1].angles} else {a.angles};
+                let after = if i + 2 < keys.len() {keys[i + 2].angles} else {b.angles};
+                catmull_rom(before, a.angles, b.angles, after, t)
+            }
+        }
+    }
+}
+
+impl Clip {
+    pub fn new(interp: Interp, mode: PlayMode) -> Clip {
+        Clip {tracks: vec![], interp, mode}
+    }
+
+    // add one key, keys can come in any order
+    pub fn key(&mut self, target: &str, time: f64, angles: V3) -> &mut Clip {
+        let i = match self.tracks.iter().position(|t| t.target == target) {
+            Some(i) => i,
+            None => {
+                self.tracks.push(Track {target: target.to_string(), keys: vec![]});
+                self.tracks.len() - 1
+            }
+        };
+        let keys = &mut self.tracks[i].keys;
+        let at = keys.iter().position(|k| k.time > time).unwrap_or(keys.len());
+        keys.insert(at, Keyframe {time, angles});
+        self
+    }
+
+    // a clip that moves through whole poses, every joint any of the poses
+    // mention gets a key at every time (rest angles where a pose skips it)
+    pub fn from_poses(poses: &[(f64, &Pose)], interp: Interp, mode: PlayMode) -> Clip {
+        let mut clip = Clip::new(interp, mode);
+        let mut joints: Vec<&str> = vec![];
+        for (_, pose) in poses {
+            for (name, _) in pose.iter() {
+                if !joints.contains(name) {
+                    joints.push(name);
+                }
+            }
+        }
+
+        for (time, pose) in poses {
+            for joint in &joints {
+                let angles = pose.iter().find(|p| p.0 == *joint).map(|p| p.1).unwrap_or(V3::ZERO);
+                clip.key(joint, *time, angles);
+            }
+        }
+        clip
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.tracks.iter().filter_map(|t| t.keys.last()).map(|k| k.time).fold(0.0, f64::max)
+    }
+
+    // wall clock time -> time inside the clip, depending on the play mode
+    pub fn local_time(&self, time: f64) -> f64 {
+        let d = self.duration();
+        if d <= 0.0 {
+            return 0.0;
+        }
+        match self.mode {
+            PlayMode::Once => time.clamp(0.0, d),
+            PlayMode::Loop => time.rem_euclid(d),
+            PlayMode::PingPong => {
+                let t = time.rem_euclid(2.0 * d);
+                if t > d {2.0 * d - t} else {t}
+            }
+        }
+    }
+
+    pub fn sample(&self, time: f64) -> Vec<(&str, V3)> {
+        let t = self.local_time(time);
+        self.tracks.iter().map(|track| (track.target.as_str(), track.sample(t, self.interp))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(interp: Interp, mode: PlayMode) -> Clip {
+        let mut clip = Clip::new(interp, mode);
+        clip.key("j", 2.0, V3 {x: 2.0, y: 0.0, z: 0.0})
+            .key("j", 0.0, V3 {x: 0.0, y: 0.0, z: 0.0})
+            .key("j", 1.0, V3 {x: 1.0, y: 0.0, z: 0.0});
+        clip
+    }
+
+    #[test]
+    fn interpolation_hits_the_keys() {
+        for interp in [Interp::Linear, Interp::Cubic, Interp::Ease, Interp::Slerp] {
+            let c = clip(interp, PlayMode::Once);
+            for t in [0.0, 1.0, 2.0] {
+                assert!((c.sample(t)[0].1.x - t).abs() < 1e-9);
+            }
+        }
+        assert!((clip(Interp::Linear, PlayMode::Once).sample(0.25)[0].1.x - 0.25).abs() < 1e-9);
+        // ease starts slower than linear
+        assert!(clip(Interp::Ease, PlayMode::Once).sample(0.25)[0].1.x < 0.25);
+    }
+
+    #[test]
+    fn play_modes() {
+        assert_eq!(clip(Interp::Linear, PlayMode::Once).local_time(5.0), 2.0);
+        assert!((clip(Interp::Linear, PlayMode::Loop).local_time(2.5) - 0.5).abs() < 1e-9);
+        assert!((clip(Interp::Linear, PlayMode::PingPong).local_time(2.5) - 1.5).abs() < 1e-9);
+        assert!((clip(Interp::Linear, PlayMode::PingPong).local_time(4.5) - 0.5).abs() < 1e-9);
+    }
+}
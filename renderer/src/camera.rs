@@ -0,0 +1,264 @@
+// the camera: where it is, which way it looks, and the lens (fov, near, far)
+// two ways to drive it from the window:
+//   orbit - circles around a target point, drag to orbit, right drag to pan,
+//           scroll or +/- to zoom, arrow keys orbit too
+//   fly   - WASD to move, space/left shift for up/down, drag to look around
+// tab switches between them and R puts everything back
+
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window};
+
+use crate::math::{Mat4, V3, V4};
+use crate::{ASPECT_RATIO, FAR, FOV, NEAR};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CameraMode {Orbit, Fly}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub position: V3,
+    pub yaw: f64,   // around y, 0 looks down +z
+    pub pitch: f64, // around x, positive looks up
+    pub fov: f64,
+    pub near: f64,
+    pub far: f64,
+
+    pub mode: CameraMode,
+    pub target: V3,    // what orbit mode circles around
+    pub distance: f64, // and how far away it stays
+
+    last_mouse: Option<(f32, f32)>,
+}
+
+// how fast things move, per second or per pixel of mouse drag
+const ORBIT_KEY_SPEED: f64 = 1.5;
+const MOUSE_SPEED: f64 = 0.005;
+const FLY_SPEED: f64 = 100.0;
+const ZOOM_STEP: f64 = 1.1;
+
+impl Default for Camera {
+    // the old fixed camera, 150 units back looking at the origin
+    fn default() -> Camera {
+        let mut camera = Camera {
+            position: V3::ZERO,
+            yaw: 0.0,
+            pitch: 0.0,
+            fov: FOV,
+            near: NEAR,
+            far: FAR,
+            mode: CameraMode::Orbit,
+            target: V3::ZERO,
+            distance: 150.0,
+            last_mouse: None,
+        };
+        camera.update_orbit();
+        camera
+    }
+}
+
+impl Camera {
+    pub fn forward(&self) -> V3 {
+        V3 {
+            x: self.yaw.sin() * self.pitch.cos(),
+            y: self.pitch.sin(),
+            z: self.yaw.cos() * self.pitch.cos(),
+        }
+    }
+
+    pub fn right(&self) -> V3 {
+        V3 {x: self.yaw.cos(), y: 0.0, z: -self.yaw.sin()}
+    }
+
+    pub fn up(&self) -> V3 {
+        V3 {
+            x: -self.yaw.sin() * self.pitch.sin(),
+            y: self.pitch.cos(),
+            z: -self.yaw.cos() * self.pitch.sin(),
+        }
+    }
+
+    // world space -> camera space, x right, y up, z straight ahead
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at(self.position, self.position + self.forward(), V3::new(0.0, 1.0, 0.0))
+    }
+
+    pub fn projection(&self) -> Mat4 {
+        Mat4::perspective(self.fov, ASPECT_RATIO, self.near, self.far)
+    }
+
+    // world space straight to clip space, ready for the clipper
+    // building it isn't free, anything drawing lots of points gets it once
+    // and uses it for all of them
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection() * self.view()
+    }
+
+    // one point to clip space, for the odd point here and there
+    pub fn world_to_clip(&self, v: V3) -> V4 {
+        self.view_projection().transform_v4(v)
+    }
+
+    // puts the camera back on its orbit around the target
+    fn update_orbit(&mut self) {
+        self.position = self.target - self.forward() * self.distance;
+    }
+
+    // turning, clamped so we never flip over the top
+    fn turn(&mut self, dyaw: f64, dpitch: f64) {
+        let limit = 89.0f64.to_radians();
+        self.yaw += dyaw;
+        self.pitch = (self.pitch + dpitch).clamp(-limit, limit);
+    }
+
+    pub fn orbit(&mut self, dyaw: f64, dpitch: f64) {
+        self.turn(dyaw, dpitch);
+        self.update_orbit();
+    }
+
+    // slides the camera and the target together, in screen directions
+    pub fn pan(&mut self, dx: f64, dy: f64) {
+        let (right, up) = (self.right(), self.up());
+        self.target += right * dx + up * dy;
+        self.update_orbit();
+    }
+
+    // factor < 1 moves in, > 1 moves out
+    pub fn zoom(&mut self, factor: f64) {
+        self.distance = (self.distance * factor).clamp(self.near * 10.0, self.far / 2.0);
+        self.update_orbit();
+    }
+
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == CameraMode::Orbit && self.mode == CameraMode::Fly {
+            // orbit around whatever is in front of us, same distance as before
+            self.target = self.position + self.forward() * self.distance;
+        }
+        self.mode = mode;
+    }
+
+    // reads the keyboard and mouse for one frame, dt in seconds
+    pub fn handle_input(&mut self, window: &Window, dt: f64) {
+        if window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            let mode = if self.mode == CameraMode::Orbit {CameraMode::Fly} else {CameraMode::Orbit};
+            self.set_mode(mode);
+        }
+        if window.is_key_pressed(Key::R, KeyRepeat::No) {
+            *self = Camera::default();
+        }
+
+        // mouse drags, measured from where the mouse was last frame
+        let mouse = window.get_mouse_pos(MouseMode::Pass);
+        let (dx, dy) = match (mouse, self.last_mouse) {
+            (Some(m), Some(l)) => ((m.0 - l.0) as f64, (m.1 - l.1) as f64),
+            _ => (0.0, 0.0),
+        };
+        self.last_mouse = mouse;
+        let left = window.get_mouse_down(MouseButton::Left);
+        let right = window.get_mouse_down(MouseButton::Right);
+
+        let key = |k: Key| if window.is_key_down(k) {1.0} else {0.0};
+
+        match self.mode {
+            CameraMode::Orbit => {
+                if left {
+                    self.orbit(dx * MOUSE_SPEED, -dy * MOUSE_SPEED);
+                }
+                if right {
+                    // pan speed follows the distance so it feels the same up close
+                    let s = self.distance * MOUSE_SPEED * 0.2;
+                    self.pan(-dx * s, dy * s);
+                }
+                let yaw = key(Key::Right) - key(Key::Left);
+                let pitch = key(Key::Up) - key(Key::Down);
+                if yaw != 0.0 || pitch != 0.0 {
+                    self.orbit(yaw * ORBIT_KEY_SPEED * dt, pitch * ORBIT_KEY_SPEED * dt);
+                }
+                if let Some((_, scroll)) = window.get_scroll_wheel() {
+                    self.zoom(if scroll > 0.0 {1.0 / ZOOM_STEP} else {ZOOM_STEP});
+                }
+                if window.is_key_pressed(Key::Equal, KeyRepeat::Yes) {
+                    self.zoom(1.0 / ZOOM_STEP);
+                }
+                if window.is_key_pressed(Key::Minus, KeyRepeat::Yes) {
+                    self.zoom(ZOOM_STEP);
+                }
+            }
+            CameraMode::Fly => {
+                if left {
+                    self.turn(dx * MOUSE_SPEED, -dy * MOUSE_SPEED);
+                }
+                let step = FLY_SPEED * dt;
+                let (forward, right) = (self.forward(), self.right());
+                self.position += forward * ((key(Key::W) - key(Key::S)) * step);
+                self.position += right * ((key(Key::D) - key(Key::A)) * step);
+                self.position.y += (key(Key::Space) - key(Key::LeftShift)) * step;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: V3, b: V3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn orbit_circles_the_target() {
+        let mut camera = Camera::default();
+        assert!(close(camera.position, V3::new(0.0, 0.0, -150.0)));
+        camera.orbit(std::f64::consts::FRAC_PI_2, 0.0);
+        // a quarter turn round puts it off to the side, still looking at the target
+        assert!(close(camera.position, V3::new(-150.0, 0.0, 0.0)));
+        assert!(close(camera.forward(), V3::new(1.0, 0.0, 0.0)));
+        // and it never goes over the top
+        camera.orbit(0.0, 10.0);
+        assert!((camera.pitch - 89.0f64.to_radians()).abs() < 1e-9);
+        assert!(((camera.position - camera.target).length() - 150.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn pan_and_zoom() {
+        let mut camera = Camera::default();
+        camera.orbit(0.4, 0.3);
+        let (before, forward) = (camera.position, camera.forward());
+        camera.pan(10.0, 5.0);
+        let moved = camera.right() * 10.0 + camera.up() * 5.0;
+        assert!(close(camera.target, moved));
+        assert!(close(camera.position, before + moved));
+        assert!(close(camera.forward(), forward));
+
+        camera.zoom(0.5);
+        assert_eq!(camera.distance, 75.0);
+        assert!(close(camera.position, camera.target - forward * 75.0));
+        camera.zoom(1e-9);
+        assert_eq!(camera.distance, camera.near * 10.0);
+        camera.zoom(1e9);
+        assert_eq!(camera.distance, camera.far / 2.0);
+    }
+
+    #[test]
+    fn fly_then_orbit() {
+        let mut camera = Camera::default();
+        camera.set_mode(CameraMode::Fly);
+        camera.position = V3::new(10.0, 20.0, 30.0);
+        camera.turn(-std::f64::consts::FRAC_PI_2, 0.0);
+        // back to orbit circles whatever is straight ahead, as far off as before
+        camera.set_mode(CameraMode::Orbit);
+        assert!(close(camera.target, V3::new(10.0 - 150.0, 20.0, 30.0)));
+    }
+
+    #[test]
+    fn clip_space() {
+        let mut camera = Camera::default();
+        camera.orbit(0.7, -0.2);
+        // the target is dead center, and in front so w is positive
+        let c = camera.world_to_clip(camera.target);
+        assert!(c.x.abs() < 1e-9 && c.y.abs() < 1e-9 && c.w > 0.0);
+        let p = V3::new(12.0, -7.0, 3.0);
+        let a = camera.world_to_clip(p);
+        let b = (camera.projection() * camera.view()).transform_v4(p);
+        assert!((a.x - b.x).abs() + (a.y - b.y).abs() + (a.z - b.z).abs() + (a.w - b.w).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,207 @@
+// clipping in homogeneous clip space, before the perspective divide
+// anything behind the camera has w <= 0 there and dividing by it is what used
+// to send vertices flying off to the middle of the screen, so we cut the
+// triangles against the view frustum first and only divide what survives
+
+use crate::math::V4;
+use crate::{HEIGHT, WIDTH};
+
+// the six sides of the frustum, in clip space each one is just -w <= c <= w
+#[derive(Clone, Copy)]
+pub enum Plane {Left, Right, Bottom, Top, Near, Far}
+
+pub const ALL_PLANES: [Plane; 6] = [Plane::Near, Plane::Far, Plane::Left, Plane::Right, Plane::Bottom, Plane::Top];
+
+// the wireframe only needs the depth planes, the line drawer already skips
+// pixels that are off screen and clipping x/y would add border edges
+pub const DEPTH_PLANES: [Plane; 2] = [Plane::Near, Plane::Far];
+
+impl Plane {
+    // signed distance-ish value, >= 0 means the point is on the inside
+    fn dist(&self, p: V4) -> f64 {
+        match self {
+            Plane::Left => p.w + p.x,
+            Plane::Right => p.w - p.x,
+            Plane::Bottom => p.w + p.y,
+            Plane::Top => p.w - p.y,
+            Plane::Near => p.w + p.z,
+            Plane::Far => p.w - p.z,
+        }
+    }
+}
+
+// anything that can go through the clipper: it needs a clip space position
+// and a way to make the in-between vertex where an edge crosses a plane,
+// so colors or normals riding along get cut at the same spot
+pub trait ClipVertex: Copy {
+    fn clip_pos(&self) -> V4;
+    fn lerp(self, o: Self, t: f64) -> Self;
+}
+
+impl ClipVertex for V4 {
+    fn clip_pos(&self) -> V4 {
+        *self
+    }
+
+    fn lerp(self, o: V4, t: f64) -> V4 {
+        V4::lerp(self, o, t)
+    }
+}
+
+pub fn is_inside(p: V4, planes: &[Plane]) -> bool {
+    planes.iter().all(|plane| plane.dist(p) >= 0.0)
+}
+
+// perspective divide + viewport, gives back (x, y, 1/z) like project_3D
+pub fn to_screen(p: V4) -> (f64, f64, f64) {
+    let x_ndc = p.x / p.w;
+    let y_ndc = -p.y / p.w; // screen y goes down
+
+    let x_screen = (x_ndc * WIDTH as f64 / 2.0) + WIDTH as f64 / 2.0;
+    let y_screen = (y_ndc * HEIGHT as f64 / 2.0) + HEIGHT as f64 / 2.0;
+
+    (x_screen, y_screen, 1.0 / p.w)
+}
+
+// Sutherland-Hodgman, one plane at a time
+// every plane can add at most one vertex so a triangle comes out with 9 tops
+pub fn clip_polygon<T: ClipVertex>(mut poly: Vec<T>, planes: &[Plane]) -> Vec<T> {
+    for plane in planes {
+        if poly.is_empty() {
+            break;
+        }
+
+        let mut out = Vec::with_capacity(poly.len() + 1);
+        for i in 0..poly.len() {
+            let cur = poly[i];
+            let next = poly[(i + 1) % poly.len()];
+            let d_cur = plane.dist(cur.clip_pos());
+            let d_next = plane.dist(next.clip_pos());
+
+            if d_cur >= 0.0 {
+                out.push(cur);
+            }
+            // the edge crosses the plane, keep the crossing point
+            if (d_cur >= 0.0) != (d_next >= 0.0) {
+                out.push(cur.lerp(next, d_cur / (d_cur - d_next)));
+            }
+        }
+        poly = out;
+    }
+    poly
+}
+
+// clip a triangle and fan whatever is left back into triangles
+pub fn clip_triangle<T: ClipVertex>(a: T, b: T, c: T, planes: &[Plane]) -> Vec<[T; 3]> {
+    // fast path, most triangles are completely on screen
+    if [a, b, c].iter().all(|v| is_inside(v.clip_pos(), planes)) {
+        return vec![[a, b, c]];
+    }
+
+    let poly = clip_polygon(vec![a, b, c], planes);
+    let mut out = vec![];
+    for i in 1..poly.len().saturating_sub(1) {
+        out.push([poly[0], poly[i], poly[i + 1]]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{V2, V3};
+
+    // a position with a uv and a color riding along, like the rasterizer's vertices
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Vert {pos: V4, uv: V2, color: V3}
+
+    impl ClipVertex for Vert {
+        fn clip_pos(&self) -> V4 {
+            self.pos
+        }
+
+        fn lerp(self, o: Vert, t: f64) -> Vert {
+            Vert {pos: self.pos.lerp(o.pos, t), uv: self.uv.lerp(o.uv, t), color: self.color.lerp(o.color, t)}
+        }
+    }
+
+    fn vert(x: f64, y: f64, z: f64, w: f64, uv: V2, color: V3) -> Vert {
+        Vert {pos: V4 {x, y, z, w}, uv, color}
+    }
+
+    fn close(a: Vert, b: Vert) -> bool {
+        let (p, q) = (a.pos, b.pos);
+        let pos = (p.x - q.x).abs() + (p.y - q.y).abs() + (p.z - q.z).abs() + (p.w - q.w).abs();
+        pos < 1e-9 && (a.uv - b.uv).x.abs() + (a.uv - b.uv).y.abs() < 1e-9 && (a.color - b.color).length() < 1e-9
+    }
+
+    // a sits on the camera side of the near plane, b and c are behind it
+    // (z < -w) with w = 2 so the cut also has to interpolate w
+    fn corners() -> (Vert, Vert, Vert) {
+        (
+            vert(0.0, 0.0, 0.0, 1.0, V2::new(0.0, 0.0), V3::new(1.0, 0.0, 0.0)),
+            vert(2.0, 0.0, -3.0, 2.0, V2::new(1.0, 0.0), V3::new(0.0, 1.0, 0.0)),
+            vert(0.0, 2.0, -3.0, 2.0, V2::new(0.0, 1.0), V3::new(0.0, 0.0, 1.0)),
+        )
+    }
+
+    #[test]
+    fn one_corner_in_front_gives_one_triangle() {
+        let (a, b, c) = corners();
+        let tris = clip_triangle(a, b, c, &[Plane::Near]);
+        assert_eq!(tris.len(), 1);
+        // both edges leaving a cross the plane half way, everything riding
+        // along gets cut at the same spot
+        let ab = vert(1.0, 0.0, -1.5, 1.5, V2::new(0.5, 0.0), V3::new(0.5, 0.5, 0.0));
+        let ca = vert(0.0, 1.0, -1.5, 1.5, V2::new(0.0, 0.5), V3::new(0.5, 0.0, 0.5));
+        let [p, q, r] = tris[0];
+        assert!(p == a && close(q, ab) && close(r, ca), "{:?}", tris[0]);
+        for v in [q, r] {
+            assert!(Plane::Near.dist(v.pos).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn two_corners_in_front_give_two_triangles() {
+        let (a, _, c) = corners();
+        let b = vert(2.0, 0.0, 0.0, 2.0, V2::new(1.0, 0.0), V3::new(0.0, 1.0, 0.0));
+        let poly = clip_polygon(vec![a, b, c], &[Plane::Near]);
+        // b -> c crosses two thirds of the way along, c -> a half way
+        let bc = vert(2.0 / 3.0, 4.0 / 3.0, -2.0, 2.0, V2::new(1.0 / 3.0, 2.0 / 3.0), V3::new(0.0, 1.0 / 3.0, 2.0 / 3.0));
+        let ca = vert(0.0, 1.0, -1.5, 1.5, V2::new(0.0, 0.5), V3::new(0.5, 0.0, 0.5));
+        assert_eq!(poly.len(), 4);
+        assert!(poly[0] == a && poly[1] == b && close(poly[2], bc) && close(poly[3], ca), "{:?}", poly);
+
+        let tris = clip_triangle(a, b, c, &[Plane::Near]);
+        assert_eq!(tris.len(), 2);
+        assert!(tris[0][0] == a && tris[0][1] == b && close(tris[0][2], bc));
+        assert!(tris[1][0] == a && close(tris[1][1], bc) && close(tris[1][2], ca));
+    }
+
+    #[test]
+    fn all_behind_is_gone_and_all_inside_is_untouched() {
+        let (_, b, c) = corners();
+        let d = vert(1.0, 1.0, -5.0, 1.0, V2::new(1.0, 1.0), V3::new(1.0, 1.0, 1.0));
+        assert!(clip_triangle(b, c, d, &[Plane::Near]).is_empty());
+        assert!(clip_polygon(vec![b, c, d], &ALL_PLANES).is_empty());
+
+        let (a, ..) = corners();
+        let e = vert(0.5, 0.0, 0.5, 1.0, V2::new(1.0, 0.0), V3::new(0.0, 1.0, 0.0));
+        let f = vert(0.0, -0.5, 0.9, 1.0, V2::new(0.0, 1.0), V3::new(0.0, 0.0, 1.0));
+        assert_eq!(clip_triangle(a, e, f, &ALL_PLANES), vec![[a, e, f]]);
+        assert_eq!(clip_polygon(vec![a, e, f], &ALL_PLANES), vec![a, e, f]);
+    }
+
+    #[test]
+    fn what_comes_out_is_inside_every_plane() {
+        // a big triangle poking out of every side of the frustum at once
+        let (a, ..) = corners();
+        let b = vert(9.0, -4.0, 3.0, 1.0, V2::new(1.0, 0.0), V3::new(0.0, 1.0, 0.0));
+        let c = vert(-6.0, 7.0, -8.0, 1.0, V2::new(0.0, 1.0), V3::new(0.0, 0.0, 1.0));
+        let tris = clip_triangle(a, b, c, &ALL_PLANES);
+        assert!(!tris.is_empty());
+        for v in tris.iter().flatten() {
+            assert!(ALL_PLANES.iter().all(|p| p.dist(v.pos) > -1e-9), "{:?}", v);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// back-face culling: on a closed model every triangle facing away from the
+// camera is hidden behind one facing it, so skipping them halves the work
+// and nothing changes on screen
+// which side is the front comes from the winding, the order the corners go
+// around in when you look at the triangle from outside the model
+// the generators all wind clockwise (the face_normal convention), obj files
+// from right handed tools come out the same way once they're in our left
+// handed world
+
+use crate::math::V3;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cull {Back, Front, None}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Winding {Clockwise, CounterClockwise}
+
+impl Cull {
+    pub fn parse(s: &str) -> Option<Cull> {
+        match s {
+            "back" => Some(Cull::Back),
+            "front" => Some(Cull::Front),
+            "none" => Some(Cull::None),
+            _ => None,
+        }
+    }
+}
+
+impl Winding {
+    pub fn parse(s: &str) -> Option<Winding> {
+        match s {
+            "cw" => Some(Winding::Clockwise),
+            "ccw" => Some(Winding::CounterClockwise),
+            _ => None,
+        }
+    }
+}
+
+// what back faces get painted when show_back_faces is on, loud on purpose
+pub const BACK_FACE_COLOR: u32 = 0xFF00FF;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Culling {
+    pub cull: Cull,
+    pub winding: Winding,
+    // debug overlay: draw the back faces anyway, in BACK_FACE_COLOR, so a
+    // triangle wound the wrong way sticks out
+    pub show_back_faces: bool,
+}
+
+impl Default for Culling {
+    fn default() -> Culling {
+        Culling {cull: Cull::Back, winding: Winding::Clockwise, show_back_faces: false}
+    }
+}
+
+impl Culling {
+    #[cfg(test)]
+    pub fn none() -> Culling {
+        Culling {cull: Cull::None, ..Culling::default()}
+    }
+
+    // does the triangle with this (clockwise) normal show its front to the eye
+    // seen edge on, or with no area and so a zero normal, there's no front to
+    // see whichever way it winds
+    pub fn is_front(&self, normal: V3, center: V3, eye: V3) -> bool {
+        let facing = normal.dot(eye - center);
+        match self.winding {
+            Winding::Clockwise => facing > 0.0,
+            Winding::CounterClockwise => facing < 0.0,
+        }
+    }
+
+    // whether a triangle facing this way gets drawn at all
+    pub fn keep(&self, front: bool) -> bool {
+        if !front && self.show_back_faces {
+            return true;
+        }
+        match self.cull {
+            Cull::Back => front,
+            Cull::Front => !front,
+            Cull::None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_triangle_from_vecs;
+
+    // the camera sits at -z looking down +z like the default one
+    const EYE: V3 = V3::new(0.0, 0.0, -100.0);
+
+    fn facing(culling: &Culling, v0: V3, v1: V3, v2: V3) -> bool {
+        let tri = get_triangle_from_vecs(v0, v1, v2, 0);
+        culling.is_front(tri.face_normal(), tri.center(), EYE)
+    }
+
+    #[test]
+    fn winding_decides_the_front() {
+        // clockwise on screen seen from the eye, and the same corners the other way round
+        let (a, b, c) = (V3::new(0.0, 0.0, 0.0), V3::new(0.0, 10.0, 0.0), V3::new(10.0, 0.0, 0.0));
+        let cw = Culling::default();
+        let ccw = Culling {winding: Winding::CounterClockwise, ..cw};
+        assert!(facing(&cw, a, b, c) && !facing(&cw, a, c, b));
+        assert!(!facing(&ccw, a, b, c) && facing(&ccw, a, c, b));
+        // from behind it all flips
+        let tri = get_triangle_from_vecs(a, b, c, 0);
+        assert!(!cw.is_front(tri.face_normal(), tri.center(), -EYE));
+        assert!(ccw.is_front(tri.face_normal(), tri.center(), -EYE));
+    }
+
+    #[test]
+    fn flat_and_edge_on_triangles_have_no_front() {
+        let (a, b) = (V3::new(0.0, 0.0, 0.0), V3::new(10.0, 0.0, 0.0));
+        let winding = [Winding::Clockwise, Winding::CounterClockwise];
+        for culling in winding.map(|winding| Culling {winding, ..Culling::default()}) {
+            // all three corners on a line
+            assert!(!facing(&culling, a, b, V3::new(20.0, 0.0, 0.0)));
+            // a real triangle, but lying along the line of sight
+            assert!(!facing(&culling, a, b, V3::new(0.0, 0.0, 10.0)));
+        }
+    }
+
+    #[test]
+    fn what_each_mode_keeps() {
+        for winding in [Winding::Clockwise, Winding::CounterClockwise] {
+            let with = |cull, show_back_faces| Culling {cull, winding, show_back_faces};
+            // (front, back)
+            assert_eq!([true, false].map(|f| with(Cull::Back, false).keep(f)), [true, false]);
+            assert_eq!([true, false].map(|f| with(Cull::Front, false).keep(f)), [false, true]);
+            assert_eq!([true, false].map(|f| with(Cull::None, false).keep(f)), [true, true]);
+            // the overlay brings the back faces back whatever gets culled
+            assert_eq!([true, false].map(|f| with(Cull::Back, true).keep(f)), [true, true]);
+            assert_eq!([true, false].map(|f| with(Cull::Front, true).keep(f)), [false, true]);
+            assert_eq!([true, false].map(|f| with(Cull::None, true).keep(f)), [true, true]);
+        }
+    }
+
+    #[test]
+    fn flags() {
+        assert_eq!(Cull::parse("front"), Some(Cull::Front));
+        assert_eq!(Cull::parse("sideways"), None);
+        assert_eq!(Winding::parse("ccw"), Some(Winding::CounterClockwise));
+        assert_eq!(Winding::parse("CW"), None);
+    }
+}
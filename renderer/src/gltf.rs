@@ -0,0 +1,490 @@
+// glTF 2.0 scenes, the .gltf json plus the .bin files it points at
+// the json is a pile of arrays that refer to each other by index:
+//   nodes     - a tree, each with a transform and maybe a mesh
+//   meshes    - primitives, each a list of attributes (POSITION, NORMAL,
+//               COLOR_0) and maybe indices and a material
+//   accessors - typed views into the binary data ("count VEC3s of f32")
+//   bufferViews / buffers - where in which file those bytes are
+//   materials - we take the base color and roughness, not the textures
+// every node with a mesh becomes a named part, in the tree's world space,
+// so a rigged hand comes in with its finger names like the hand file's parts
+// .glb (everything in one binary file) isn't supported, only .gltf, with the
+// buffers next to it or embedded as base64 data: uris
+// corners go round the same way as in obj files
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::light::{color_to_v3, v3_to_color, Material};
+use crate::math::{Mat4, V3};
+use crate::obj::{add_part, Parts};
+use crate::quat::Quat;
+use crate::{get_triangle_from_vecs, smooth_normals, transform_triangles, Triangle3d};
+
+// the most elements an accessor can have, counts come straight from the file
+// and one with no buffer view would otherwise get that many zeros made up
+const MAX_COUNT: usize = 1 << 24;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Gltf {
+    asset: Asset,
+    scene: Option<usize>,
+    #[serde(default)]
+    scenes: Vec<Scene>,
+    #[serde(default)]
+    nodes: Vec<Node>,
+    #[serde(default)]
+    meshes: Vec<GltfMesh>,
+    #[serde(default)]
+    accessors: Vec<Accessor>,
+    #[serde(default)]
+    buffer_views: Vec<BufferView>,
+    #[serde(default)]
+    buffers: Vec<Buffer>,
+    #[serde(default)]
+    materials: Vec<GltfMaterial>,
+}
+
+#[derive(Deserialize)]
+struct Asset {version: String}
+
+#[derive(Deserialize)]
+struct Scene {
+    #[serde(default)]
+    nodes: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    name: Option<String>,
+    #[serde(default)]
+    children: Vec<usize>,
+    mesh: Option<usize>,
+    matrix: Option<[f64; 16]>,
+    translation: Option<[f64; 3]>,
+    rotation: Option<[f64; 4]>, // x, y, z, w
+    scale: Option<[f64; 3]>,
+}
+
+#[derive(Deserialize)]
+struct GltfMesh {
+    name: Option<String>,
+    primitives: Vec<Primitive>,
+}
+
+#[derive(Deserialize)]
+struct Primitive {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    #[serde(default = "triangles")]
+    mode: u32,
+}
+
+fn triangles() -> u32 {
+    4
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GltfMaterial {
+    #[serde(default)]
+    pbr_metallic_roughness: Pbr,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Pbr {
+    base_color_factor: [f64; 4],
+    roughness_factor: f64,
+}
+
+impl Default for Pbr {
+    fn default() -> Pbr {
+        Pbr {base_color_factor: [1.0; 4], roughness_factor: 1.0}
+    }
+}
+
+pub fn load_gltf(path: &Path, default_color: u32) -> Result<(Vec<Triangle3d>, Parts), String> {
+    let src = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let load = |uri: &str| {
+        let file = dir.join(uri);
+        fs::read(&file).map_err(|e| format!("{}: {}", file.display(), e))
+    };
+    parse_gltf(&src, load, default_color).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// `load` fetches the buffer files by uri, data: uris are decoded here
+pub fn parse_gltf(src: &str, load: impl Fn(&str) -> Result<Vec<u8>, String>, default_color: u32) -> Result<(Vec<Triangle3d>, Parts), String> {
+    let gltf: Gltf = serde_json::from_str(src).map_err(|e| e.to_string())?;
+    if !gltf.asset.version.starts_with("2.") {
+        return Err(format!("glTF version {}, only 2.x is supported", gltf.asset.version));
+    }
+
+    let mut buffers = vec![];
+    for (i, buffer) in gltf.buffers.iter().enumerate() {
+        let bytes = match buffer.uri.as_deref() {
+            None => return Err(format!("buffer {} has no uri, .glb files aren't supported", i)),
+            Some(uri) if uri.starts_with("data:") => {
+                let data = uri.split_once(";base64,").ok_or(format!("buffer {}: only base64 data uris are supported", i))?.1;
+                base64_decode(data).ok_or(format!("buffer {}: bad base64", i))?
+            }
+            Some(uri) => load(uri)?,
+        };
+        if bytes.len() < buffer.byte_length {
+            return Err(format!("buffer {} should be {} bytes but is {}", i, buffer.byte_length, bytes.len()));
+        }
+        buffers.push(bytes);
+    }
+
+    // the scene to show, or when there are no scenes every node nobody has as a child
+    let roots: Vec<usize> = match gltf.scenes.get(gltf.scene.unwrap_or(0)) {
+        Some(scene) => scene.nodes.clone(),
+        None => (0..gltf.nodes.len()).filter(|i| !gltf.nodes.iter().any(|n| n.children.contains(i))).collect(),
+    };
+
+    let mut out = vec![];
+    let mut parts: Parts = vec![];
+    // (node, its parent's world matrix, how deep), depth first
+    let mut stack: Vec<(usize, Mat4, usize)> = roots.iter().rev().map(|&n| (n, Mat4::IDENTITY, 0)).collect();
+    while let Some((index, parent, depth)) = stack.pop() {
+        let node = gltf.nodes.get(index).ok_or(format!("there's no node {}", index))?;
+        if depth > gltf.nodes.len() {
+            return Err(format!("node {} is its own ancestor", index));
+        }
+        let world = parent * local_matrix(node);
+        for &child in node.children.iter().rev() {
+            stack.push((child, world, depth + 1));
+        }
+
+        let Some(m) = node.mesh else { continue };
+        let mesh = gltf.meshes.get(m).ok_or(format!("node {} uses mesh {} which doesn't exist", index, m))?;
+        let mut triangles = vec![];
+        for primitive in &mesh.primitives {
+            triangles.extend(read_primitive(&gltf, &buffers, primitive, default_color)
+                .map_err(|e| format!("mesh {}: {}", m, e))?);
+        }
+
+        let name = node.name.clone().or(mesh.name.clone()).unwrap_or(format!("node_{}", index));
+        let start = out.len();
+        out.extend(transform_triangles(triangles, &world));
+        add_part(&mut parts, &name, start, out.len());
+    }
+    Ok((out, parts))
+}
+
+// either the whole matrix (stored column by column) or translate * rotate * scale
+fn local_matrix(node: &Node) -> Mat4 {
+    if let Some(a) = node.matrix {
+        let mut m = Mat4::IDENTITY;
+        for (i, v) in a.iter().enumerate() {
+            m.m[i % 4][i / 4] = *v;
+        }
+        return m;
+    }
+    let t = node.translation.map_or(V3::ZERO, |[x, y, z]| V3::new(x, y, z));
+    let [x, y, z, w] = node.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+    let s = node.scale.map_or(V3::new(1.0, 1.0, 1.0), |[x, y, z]| V3::new(x, y, z));
+    Mat4::translate(t) * Quat {w, x, y, z}.normalize().to_mat4() * Mat4::scale(s)
+}
+
+// the triangles of one primitive in the node's own space, points and lines are skipped
+fn read_primitive(gltf: &Gltf, buffers: &[Vec<u8>], primitive: &Primitive, default_color: u32) -> Result<Vec<Triangle3d>, String> {
+    // an attribute has to be one of `kinds` and go with every vertex
+    let attribute = |name: &str, kinds: &[&str]| -> Result<Option<Vec<Vec<f64>>>, String> {
+        let Some(&a) = primitive.attributes.get(name) else { return Ok(None) };
+        let values = read_accessor(gltf, buffers, a)?;
+        let kind = &gltf.accessors[a].kind;
+        if !kinds.contains(&kind.as_str()) {
+            return Err(format!("{} is a {} but it has to be a {}", name, kind, kinds.join(" or a ")));
+        }
+        Ok(Some(values))
+    };
+    let positions = attribute("POSITION", &["VEC3"])?.ok_or("a primitive without POSITION")?;
+    let normals = attribute("NORMAL", &["VEC3"])?;
+    let colors = attribute("COLOR_0", &["VEC3", "VEC4"])?;
+    for (name, values) in [("NORMAL", &normals), ("COLOR_0", &colors)] {
+        if let Some(values) = values.as_ref().filter(|v| v.len() != positions.len()) {
+            return Err(format!("{} has {} values for {} vertices", name, values.len(), positions.len()));
+        }
+    }
+    let indices: Vec<usize> = match primitive.indices {
+        Some(a) => read_accessor(gltf, buffers, a)?.iter().map(|i| i[0] as usize).collect(),
+        None => (0..positions.len()).collect(),
+    };
+    if let Some(&bad) = indices.iter().find(|&&i| i >= positions.len()) {
+        return Err(format!("index {} but there are only {} vertices", bad, positions.len()));
+    }
+
+    let corners: Vec<[usize; 3]> = match primitive.mode {
+        4 => indices.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect(),
+        // strips flip every other triangle so they all go round the same way
+        5 => (0..indices.len().saturating_sub(2))
+            .map(|i| if i % 2 == 0 {[indices[i], indices[i + 1], indices[i + 2]]} else {[indices[i + 1], indices[i], indices[i + 2]]})
+            .collect(),
+        6 => (1..indices.len().saturating_sub(1)).map(|i| [indices[0], indices[i], indices[i + 1]]).collect(),
+        _ => return Ok(vec![]),
+    };
+
+    let pbr = primitive.material.and_then(|m| gltf.materials.get(m)).map(|m| &m.pbr_metallic_roughness);
+    let base = pbr.map_or(color_to_v3(default_color), |p| V3::new(p.base_color_factor[0], p.base_color_factor[1], p.base_color_factor[2]));
+    // smooth surfaces get a tight highlight, rough ones (the default) none
+    let roughness = pbr.map_or(1.0, |p| p.roughness_factor.clamp(0.0, 1.0));
+    let v3 = |a: &[f64]| V3::new(a[0], a[1], a[2]);
+
+    let mut out = vec![];
+    for [a, b, c] in corners {
+        let tint = match &colors {
+            Some(colors) => (v3(&colors[a]) + v3(&colors[b]) + v3(&colors[c])) / 3.0,
+            None => V3::new(1.0, 1.0, 1.0),
+        };
+        let color = v3_to_color(V3::new(base.x * tint.x, base.y * tint.y, base.z * tint.z));
+        let mut tri = get_triangle_from_vecs(v3(&positions[a]), v3(&positions[b]), v3(&positions[c]), color);
+        let shiny = 1.0 - roughness;
+        tri.set_material(Material {
+            specular: V3::new(0.5, 0.5, 0.5) * shiny,
+            shininess: 1.0 + 127.0 * shiny * shiny,
+            ..Material::matte(color)
+        });
+        if let Some(normals) = &normals {
+            (tri.n0, tri.n1, tri.n2) = (v3(&normals[a]).normalize(), v3(&normals[b]).normalize(), v3(&normals[c]).normalize());
+        }
+        out.push(tri);
+    }
+    if normals.is_none() {
+        smooth_normals(&mut out);
+    }
+    Ok(out)
+}
+
+// every element of an accessor as plain numbers, normalized integers already scaled to 0..1 (or -1..1)
+fn read_accessor(gltf: &Gltf, buffers: &[Vec<u8>], index: usize) -> Result<Vec<Vec<f64>>, String> {
+    let accessor = gltf.accessors.get(index).ok_or(format!("there's no accessor {}", index))?;
+    let components = match accessor.kind.as_str() {
+        "SCALAR" => 1,
+        "VEC2" => 2,
+        "VEC3" => 3,
+        "VEC4" => 4,
+        other => return Err(format!("accessor {}: {} isn't something a mesh uses", index, other)),
+    };
+    let (size, max) = match accessor.component_type {
+        5120 => (1, i8::MAX as f64),
+        5121 => (1, u8::MAX as f64),
+        5122 => (2, i16::MAX as f64),
+        5123 => (2, u16::MAX as f64),
+        5125 => (4, u32::MAX as f64),
+        5126 => (4, 1.0),
+        other => return Err(format!("accessor {}: unknown component type {}", index, other)),
+    };
+
+    if accessor.count > MAX_COUNT {
+        return Err(format!("accessor {}: {} elements is more than the {} a mesh can have", index, accessor.count, MAX_COUNT));
+    }
+    // no buffer view means all zeros
+    let Some(view_index) = accessor.buffer_view else {
+        return Ok(vec![vec![0.0; components]; accessor.count]);
+    };
+    let view = gltf.buffer_views.get(view_index).ok_or(format!("accessor {}: there's no buffer view {}", index, view_index))?;
+    let buffer = buffers.get(view.buffer).ok_or(format!("buffer view {}: there's no buffer {}", view_index, view.buffer))?;
+    let element = components * size;
+    let stride = view.byte_stride.unwrap_or(element);
+    if stride < element {
+        return Err(format!("buffer view {}: a stride of {} is shorter than accessor {}'s {} byte elements", view_index, stride, index, element));
+    }
+    // the offsets all come from the file, a sum that overflows runs off the end too
+    let off_the_end = || format!("accessor {} runs off the end of its buffer", index);
+    let needed = match accessor.count {
+        0 => Some(0),
+        n => (n - 1).checked_mul(stride).and_then(|b| b.checked_add(element)).and_then(|b| b.checked_add(accessor.byte_offset)),
+    };
+    let view_end = view.byte_offset.checked_add(view.byte_length);
+    if needed.is_none_or(|n| n > view.byte_length) || view_end.is_none_or(|end| end > buffer.len()) {
+        return Err(off_the_end());
+    }
+
+    let start = view.byte_offset.checked_add(accessor.byte_offset).ok_or_else(off_the_end)?;
+    let bytes = &buffer[start..];
+    let read = |at: usize| {
+        let b = &bytes[at..at + size];
+        let v = match accessor.component_type {
+            5120 => b[0] as i8 as f64,
+            5121 => b[0] as f64,
+            5122 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            5123 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+        };
+        if accessor.normalized {(v / max).max(-1.0)} else {v}
+    };
+    Ok((0..accessor.count)
+        .map(|i| (0..components).map(|c| read(i * stride + c * size)).collect())
+        .collect())
+}
+
+fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    };
+    let mut out = vec![];
+    let (mut bits, mut n) = (0u32, 0);
+    for c in s.trim_end_matches('=').bytes() {
+        bits = bits << 6 | value(c)? as u32;
+        n += 6;
+        if n >= 8 {
+            n -= 8;
+            out.push((bits >> n) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one triangle in a .bin, used by two nodes: a parent moved up 10 and
+    // a child of it moved right 5 and colored red
+    const SCENE: &str = r#"{
+        "asset": {"version": "2.0"},
+        "scene": 0,
+        "scenes": [{"nodes": [0]}],
+        "nodes": [
+            {"name": "palm", "mesh": 0, "translation": [0, 10, 0], "children": [1]},
+            {"name": "finger", "mesh": 1, "translation": [5, 0, 0]}
+        ],
+        "meshes": [
+            {"primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]},
+            {"primitives": [{"attributes": {"POSITION": 0}, "indices": 1, "material": 0}]}
+        ],
+        "materials": [{"pbrMetallicRoughness": {"baseColorFactor": [1, 0, 0, 1]}}],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}
+        ],
+        "bufferViews": [
+            {"buffer": 0, "byteOffset": 0, "byteLength": 36},
+            {"buffer": 0, "byteOffset": 36, "byteLength": 6}
+        ],
+        "buffers": [{"uri": "tri.bin", "byteLength": 42}]
+    }"#;
+
+    fn tri_bin(_: &str) -> Result<Vec<u8>, String> {
+        let mut bytes = vec![];
+        for v in [0.0f32, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0] {
+            bytes.extend(v.to_le_bytes());
+        }
+        for i in [0u16, 1, 2] {
+            bytes.extend(i.to_le_bytes());
+        }
+        Ok(bytes)
+    }
+
+    #[test]
+    fn nodes_become_parts() {
+        let (tris, parts) = parse_gltf(SCENE, tri_bin, 0xFFFFFF).unwrap();
+        assert_eq!(parts, vec![("palm".to_string(), 0, 1), ("finger".to_string(), 1, 2)]);
+        assert_eq!(tris[0].v1, V3::new(0.0, 11.0, 0.0));
+        assert_eq!(tris[1].v2, V3::new(6.0, 10.0, 0.0));
+        assert_eq!((tris[0].color, tris[1].color), (0xFFFFFF, 0xFF0000));
+    }
+
+    #[test]
+    fn errors() {
+        let short = |_: &str| Ok(vec![0; 10]);
+        assert!(parse_gltf(SCENE, short, 0).unwrap_err().contains("buffer 0 should be 42 bytes but is 10"));
+        let v1 = SCENE.replace("\"2.0\"", "\"1.0\"");
+        assert!(parse_gltf(&v1, tri_bin, 0).unwrap_err().contains("only 2.x"));
+        let bad_index = SCENE.replace("\"count\": 3, \"type\": \"SCALAR\"", "\"count\": 3, \"type\": \"SCALAR\", \"byteOffset\": 2");
+        assert!(parse_gltf(&bad_index, tri_bin, 0).unwrap_err().contains("accessor 1 runs off the end"));
+    }
+
+    #[test]
+    fn sizes_that_overflow_are_errors() {
+        let huge = SCENE.replace("\"count\": 3, \"type\": \"VEC3\"", "\"count\": 18446744073709551615, \"type\": \"VEC3\"");
+        assert!(parse_gltf(&huge, tri_bin, 0).unwrap_err().contains("is more than the"));
+        let no_view = huge.replace("{\"bufferView\": 0, ", "{");
+        assert!(parse_gltf(&no_view, tri_bin, 0).unwrap_err().contains("is more than the"));
+        // right at the cap is allowed, but the 36 byte view can't hold it
+        let long = SCENE.replace("\"count\": 3, \"type\": \"VEC3\"", "\"count\": 16777216, \"type\": \"VEC3\"");
+        assert!(parse_gltf(&long, tri_bin, 0).unwrap_err().contains("accessor 0 runs off the end"));
+
+        let max = usize::MAX;
+        let far = SCENE.replace("\"count\": 3, \"type\": \"VEC3\"", &format!("\"count\": 3, \"type\": \"VEC3\", \"byteOffset\": {}", max));
+        assert!(parse_gltf(&far, tri_bin, 0).unwrap_err().contains("accessor 0 runs off the end"));
+        let far_view = SCENE.replace("\"byteOffset\": 36, \"byteLength\": 6", &format!("\"byteOffset\": {}, \"byteLength\": 6", max));
+        assert!(parse_gltf(&far_view, tri_bin, 0).unwrap_err().contains("accessor 1 runs off the end"));
+        let long_view = SCENE.replace("\"byteOffset\": 36, \"byteLength\": 6", &format!("\"byteOffset\": 36, \"byteLength\": {}", max));
+        assert!(parse_gltf(&long_view, tri_bin, 0).unwrap_err().contains("accessor 1 runs off the end"));
+        let no_stride = SCENE.replace("\"byteOffset\": 0, \"byteLength\": 36", "\"byteOffset\": 0, \"byteLength\": 36, \"byteStride\": 0");
+        assert!(parse_gltf(&no_stride, tri_bin, 0).unwrap_err().contains("a stride of 0 is shorter"));
+    }
+
+    // the first mesh gets `attribute` from a third accessor over the positions
+    fn with_attribute(attribute: &str, count: usize, kind: &str) -> String {
+        SCENE.replace("{\"POSITION\": 0}, \"indices\": 1}]}", &format!("{{\"POSITION\": 0, \"{}\": 2}}, \"indices\": 1}}]}}", attribute))
+            .replace("\"type\": \"SCALAR\"}", &format!("\"type\": \"SCALAR\"}}, {{\"bufferView\": 0, \"componentType\": 5126, \"count\": {}, \"type\": \"{}\"}}", count, kind))
+    }
+
+    #[test]
+    fn attributes_must_fit_the_vertices() {
+        assert!(parse_gltf(&with_attribute("NORMAL", 3, "VEC3"), tri_bin, 0).is_ok());
+        assert!(parse_gltf(&with_attribute("NORMAL", 1, "VEC3"), tri_bin, 0).unwrap_err().contains("NORMAL has 1 values for 3 vertices"));
+        assert!(parse_gltf(&with_attribute("COLOR_0", 1, "VEC3"), tri_bin, 0).unwrap_err().contains("COLOR_0 has 1 values for 3 vertices"));
+    }
+
+    #[test]
+    fn attributes_must_have_the_right_type() {
+        assert!(parse_gltf(&with_attribute("NORMAL", 3, "SCALAR"), tri_bin, 0).unwrap_err().contains("NORMAL is a SCALAR"));
+        assert!(parse_gltf(&with_attribute("COLOR_0", 3, "VEC2"), tri_bin, 0).unwrap_err().contains("COLOR_0 is a VEC2"));
+        let flat = SCENE.replace("\"count\": 3, \"type\": \"VEC3\"", "\"count\": 3, \"type\": \"VEC2\"");
+        assert!(parse_gltf(&flat, tri_bin, 0).unwrap_err().contains("POSITION is a VEC2 but it has to be a VEC3"));
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(base64_decode("aGFuZA==").unwrap(), b"hand");
+        assert_eq!(base64_decode("AAEC").unwrap(), [0, 1, 2]);
+        assert!(base64_decode("a*b").is_none());
+    }
+}
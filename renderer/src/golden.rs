@@ -0,0 +1,422 @@
+// golden image tests for the rasterizer
+// every test draws into an offscreen buffer and compares it with a png that
+// is checked in under tests/golden, so a refactor that moves even a pixel shows up
+//
+// to (re)generate the references after an intended change:
+//     BLESS=1 cargo test
+// when a comparison fails a diff image goes to target/golden-diff/<name>.png,
+// mismatched pixels are red and everything else is a dimmed copy of the expected image
+
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::camera::Camera;
+use crate::light::Light;
+use crate::output::write_png;
+use crate::*;
+
+// how far off a render is allowed to be before we call it a failure
+#[derive(Clone, Copy)]
+struct Tolerance {
+    per_channel: u8,   // max difference in any of r/g/b for a pixel to still count as equal
+    max_pixels: usize, // how many pixels are allowed to differ anyway
+}
+
+const EXACT: Tolerance = Tolerance {per_channel: 0, max_pixels: 0};
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn diff_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target").join("golden-diff")
+}
+
+fn read_png(path: &PathBuf) -> (Vec<u32>, usize, usize) {
+    let file = File::open(path).unwrap_or_else(|e| {
+        panic!("missing golden image {} ({}), run with BLESS=1 to create it", path.display(), e)
+    });
+    let decoder = png::Decoder::new(BufReader::new(file));
+    let mut reader = decoder.read_info().unwrap();
+    let mut data = vec![0; reader.output_buffer_size().unwrap()];
+    let info = reader.next_frame(&mut data).unwrap();
+    assert_eq!(info.color_type, png::ColorType::Rgb, "golden images are plain rgb");
+
+    let pixels = data[..info.buffer_size()]
+        .chunks(3)
+        .map(|c| (c[0] as u32) << 16 | (c[1] as u32) << 8 | c[2] as u32)
+        .collect();
+    (pixels, info.width as usize, info.height as usize)
+}
+
+fn channels_close(a: u32, b: u32, tol: u8) -> bool {
+    [16, 8, 0].iter().all(|shift| {
+        let ca = (a >> shift & 0xFF) as i32;
+        let cb = (b >> shift & 0xFF) as i32;
+        (ca - cb).abs() <= tol as i32
+    })
+}
+
+fn assert_golden(name: &str, buf: &[u32], tol: Tolerance) {
+    let path = golden_dir().join(format!("{}.png", name));
+
+    if std::env::var_os("BLESS").is_some() {
+        fs::create_dir_all(golden_dir()).unwrap();
+        write_png(&path, buf, WIDTH, HEIGHT).unwrap();
+        return;
+    }
+
+    let (expected, w, h) = read_png(&path);
+    assert_eq!((w, h), (WIDTH, HEIGHT), "{}: golden image has the wrong size", name);
+
+    let mut diff = vec![0; WIDTH * HEIGHT];
+    let mut bad = 0;
+    for i in 0..buf.len() {
+        if channels_close(buf[i], expected[i], tol.per_channel) {
+            diff[i] = expected[i] >> 2 & 0x3F3F3F;
+        } else {
+            diff[i] = RED;
+            bad += 1;
+        }
+    }
+
+    if bad > tol.max_pixels {
+        fs::create_dir_all(diff_dir()).unwrap();
+        let diff_path = diff_dir().join(format!("{}.png", name));
+        write_png(&diff_path, &diff, WIDTH, HEIGHT).unwrap();
+        panic!("{}: {} pixels differ from the golden image (allowed {}), diff written to {}",
+               name, bad, tol.max_pixels, diff_path.display());
+    }
+}
+
+#[test]
+fn golden_make_line() {
+    let mut buf = reset_screen();
+    make_line(&mut buf, 100, 100, 700, 100, WHITE); // horizontal
+    make_line(&mut buf, 100, 120, 100, 700, GREEN); // vertical
+    make_line(&mut buf, 120, 120, 700, 700, RED);   // 45 degrees
+    make_line(&mut buf, 700, 150, 150, 400, BLUE);  // shallow, drawn right to left
+    make_line(&mut buf, 400, 700, 300, 150, YELLOW); // steep, drawn bottom to top
+    make_line(&mut buf, 750, 750, 900, 900, WHITE); // runs off the screen
+    assert_golden("make_line", &buf, EXACT);
+}
+
+#[test]
+fn golden_make_line_styled() {
+    // a fan of lines at every sort of angle, plain on the left and smooth on
+    // the right, thin at the top and 4 pixels wide at the bottom
+    let mut buf = reset_screen();
+    let styles = [
+        (0.0, 0.0, LineStyle::PLAIN), (400.0, 0.0, LineStyle::SMOOTH),
+        (0.0, 400.0, LineStyle {width: 4.0, ..LineStyle::PLAIN}), (400.0, 400.0, LineStyle {width: 4.0, ..LineStyle::SMOOTH}),
+    ];
+    for (x, y, style) in styles {
+        for i in 0..12 {
+            let angle = i as f64 * std::f64::consts::PI / 12.0;
+            let (cx, cy) = (x + 200.3, y + 200.7);
+            let color = [WHITE, RED, GREEN, BLUE, YELLOW][i % 5];
+            make_line_styled(&mut buf, cx + 30.0 * angle.cos(), cy - 30.0 * angle.sin(),
+                             cx + 170.0 * angle.cos(), cy - 170.0 * angle.sin(), color, style);
+        }
+    }
+    make_line_styled(&mut buf, 700.0, 700.0, 1e9, 2e9, WHITE, LineStyle::SMOOTH); // runs off the screen
+    assert_golden("make_line_styled", &buf, EXACT);
+
+    // smooth lines split a pixel's worth of color by where the middle falls
+    let mut buf = reset_screen();
+    make_line_styled(&mut buf, 10.0, 10.5, 20.0, 10.5, WHITE, LineStyle::SMOOTH);
+    make_line_styled(&mut buf, 10.0, 21.0, 20.0, 21.0, WHITE, LineStyle::SMOOTH);
+    assert_eq!((buf[9 * WIDTH + 15], buf[10 * WIDTH + 15], buf[11 * WIDTH + 15]), (BLACK, WHITE, BLACK));
+    assert_eq!((buf[20 * WIDTH + 15], buf[21 * WIDTH + 15]), (0x808080, 0x808080));
+    // and the end pixels only get the part the line reaches
+    assert_eq!((buf[10 * WIDTH + 9], buf[10 * WIDTH + 20]), (BLACK, BLACK));
+    make_line_styled(&mut buf, 30.25, 30.5, 40.0, 30.5, WHITE, LineStyle::SMOOTH);
+    assert_eq!(buf[30 * WIDTH + 30], 0xBFBFBF);
+}
+
+#[test]
+fn golden_fill_triangle() {
+    let mut buf = reset_screen();
+    fill_triangle(&mut buf, 100, 500, 350, 200, 500, 500, GREEN); // one winding
+    fill_triangle(&mut buf, 450, 100, 750, 150, 600, 450, RED);   // the other one
+    fill_triangle(&mut buf, -50, 700, 200, 600, 100, 900, BLUE);  // partly off screen
+    assert_golden("fill_triangle", &buf, EXACT);
+}
+
+#[test]
+fn golden_make_square() {
+    let mut buf = reset_screen();
+    make_square(&mut buf, 300, 300, 100, GREEN);
+    make_square(&mut buf, 300, 300, 80, GREEN);
+    make_square(&mut buf, 300, 300, 150, YELLOW);
+    make_square(&mut buf, 600, 600, 31, WHITE); // odd size
+    assert_golden("make_square", &buf, EXACT);
+}
+
+#[test]
+fn golden_make_square_filled() {
+    let mut buf = reset_screen();
+    make_square_filled(&mut buf, 200, 200, 100, RED);
+    make_square_filled(&mut buf, 250, 250, 100, BLUE); // overlaps the red one
+    make_square_filled(&mut buf, 790, 790, 60, WHITE); // clipped by the edge
+    make_square_filled(&mut buf, 500, 500, 31, GREEN); // odd size
+    assert_golden("make_square_filled", &buf, EXACT);
+}
+
+#[test]
+fn golden_project_3D_to_2D() {
+    // the corners of a cube, each one marked with a little square
+    let mut buf = reset_screen();
+    for &x in &[-50.0, 50.0] {
+        for &y in &[-50.0, 50.0] {
+            for &z in &[-50.0, 50.0] {
+                let (px, py) = project_3D_to_2D(&Camera::default(), V3 {x, y, z}).unwrap();
+                let color = if z < 0.0 {WHITE} else {YELLOW};
+                make_square_filled(&mut buf, px as usize, py as usize, 8, color);
+            }
+        }
+    }
+    assert_golden("project_3D_to_2D", &buf, EXACT);
+
+    // the camera is at z = -150, nothing behind it gets a screen position
+    assert!(project_3D_to_2D(&Camera::default(), V3 {x: 0.0, y: 0.0, z: -200.0}).is_none());
+    assert_eq!(project_3D_to_2D(&Camera::default(), V3 {x: 0.0, y: 0.0, z: 0.0}), Some((WIDTH as i32 / 2, HEIGHT as i32 / 2)));
+}
+
+#[test]
+fn golden_make_triangle_3D_depth() {
+    // two triangles crossing each other in depth, the z-buffer has to
+    // show each one in front where it actually is in front
+    let mut buf = reset_screen();
+    let mut zbuf = reset_zbuf();
+    let a = get_triangle_from_vecs(
+        V3 {x: -80.0, y: -60.0, z: -40.0},
+        V3 {x: 80.0, y: -60.0, z: 40.0},
+        V3 {x: 0.0, y: 80.0, z: 0.0},
+        RED,
+    );
+    let b = get_triangle_from_vecs(
+        V3 {x: -80.0, y: 60.0, z: 40.0},
+        V3 {x: 80.0, y: 60.0, z: -40.0},
+        V3 {x: 0.0, y: -80.0, z: 0.0},
+        BLUE,
+    );
+    draw_3d_from_triangles(&mut buf, &mut zbuf, &Camera::default(), &Lighting::unlit(), &Culling::none(), vec![a, b]);
+    assert_golden("make_triangle_3D_depth", &buf, EXACT);
+}
+
+// eight faces around a center, all wound outwards
+fn octahedron(center: V3, color: u32) -> Vec<Triangle3d> {
+    let (x, y, z) = (V3::new(50.0, 0.0, 0.0), V3::new(0.0, 50.0, 0.0), V3::new(0.0, 0.0, 50.0));
+    let mut tris = vec![];
+    for sx in [-1.0, 1.0] {
+        for sy in [-1.0, 1.0] {
+            for sz in [-1.0, 1.0] {
+                // swap two corners on every other face so they all wind outwards
+                let (a, b) = if sx * sy * sz > 0.0 {(y * sy, z * sz)} else {(z * sz, y * sy)};
+                tris.push(get_triangle_from_vecs(center + x * sx, center + a, center + b, color));
+            }
+        }
+    }
+    tris
+}
+
+#[test]
+fn golden_make_triangle_3D_shading() {
+    // the same octahedron twice under the studio lights, faceted on the left
+    // and with averaged normals blended across the faces on the right
+    let mut buf = reset_screen();
+    let mut zbuf = reset_zbuf();
+    let camera = Camera::default();
+    draw_3d_from_triangles(&mut buf, &mut zbuf, &camera, &Lighting::studio(ShadeMode::Flat), &Culling::default(), octahedron(V3::new(-70.0, 0.0, 0.0), WHITE));
+    let mut smooth = octahedron(V3::new(70.0, 0.0, 0.0), WHITE);
+    smooth_normals(&mut smooth);
+    draw_3d_from_triangles(&mut buf, &mut zbuf, &camera, &Lighting::studio(ShadeMode::Gouraud), &Culling::default(), smooth);
+    assert_golden("make_triangle_3D_shading", &buf, EXACT);
+}
+
+#[test]
+fn golden_make_triangle_3D_phong() {
+    // per pixel blinn-phong on a shiny octahedron, the highlights sit inside
+    // the faces where gouraud could only put them on the corners
+    let mut buf = reset_screen();
+    let mut zbuf = reset_zbuf();
+    let mut tris = octahedron(V3::ZERO, RED);
+    smooth_normals(&mut tris);
+    for tri in &mut tris {
+        tri.set_material(Material {shininess: 16.0, ..Material::plastic(RED)});
+    }
+    draw_3d_from_triangles(&mut buf, &mut zbuf, &Camera::default(), &Lighting::studio(ShadeMode::Phong), &Culling::default(), tris);
+    assert_golden("make_triangle_3D_phong", &buf, EXACT);
+}
+
+#[test]
+fn golden_back_faces() {
+    // one face of the octahedron wound the wrong way round: culled it leaves a
+    // hole, with the overlay on it shows up magenta in the same spot
+    let mut tris = octahedron(V3::new(-70.0, 0.0, 0.0), WHITE);
+    let bad = &mut tris[0];
+    (bad.v1, bad.v2) = (bad.v2, bad.v1);
+    let mut shown = octahedron(V3::new(70.0, 0.0, 0.0), WHITE);
+    let bad = &mut shown[0];
+    (bad.v1, bad.v2) = (bad.v2, bad.v1);
+
+    let mut buf = reset_screen();
+    let mut zbuf = reset_zbuf();
+    let camera = Camera::default();
+    let lighting = Lighting::studio(ShadeMode::Flat);
+    draw_3d_from_triangles(&mut buf, &mut zbuf, &camera, &lighting, &Culling::default(), tris);
+    let debug = Culling {show_back_faces: true, ..Culling::default()};
+    draw_3d_from_triangles(&mut buf, &mut zbuf, &camera, &lighting, &debug, shown);
+    assert_golden("back_faces", &buf, EXACT);
+
+    // on a closed shape the back half covers exactly what the front half does
+    let coverage = |cull: cull::Cull| {
+        let mut buf = reset_screen();
+        let mut zbuf = reset_zbuf();
+        draw_3d_from_triangles(&mut buf, &mut zbuf, &camera, &Lighting::unlit(),
+                               &Culling {cull, ..Culling::default()}, octahedron(V3::ZERO, RED));
+        buf.iter().filter(|&&c| c == RED).count()
+    };
+    assert!(coverage(cull::Cull::Back) > 0);
+    assert_eq!(coverage(cull::Cull::Back), coverage(cull::Cull::Front));
+}
+
+#[test]
+fn golden_get_box_triangles() {
+    // a cube around the origin (the old usize version couldn't even make one)
+    // in the spinning cube's colors, turned so three faces show
+    let cube = get_box_triangles(V3::ZERO, V3::new(50.0, 50.0, 50.0), SPINNING_CUBE_COLORS);
+    assert_eq!(cube.len(), 12);
+    for tri in &cube {
+        assert!(tri.face_normal().dot(tri.center()) > 0.0, "{:?} points inwards", tri);
+    }
+    assert_eq!(get_cube_triangles(10.0, -5.0, 0.0, 0.0, RED)[0].v0, V3::new(-15.0, -10.0, -10.0));
+    // mirrored and squashed it's still a closed box facing out
+    let mirrored = transform_triangles(cube.clone(), &Mat4::scale(V3::new(-1.0, 0.5, 2.0)));
+    for tri in &mirrored {
+        assert!(tri.face_normal().dot(tri.center()) > 0.0, "{:?} points inwards", tri);
+        assert!((tri.n0 - tri.face_normal()).length() < 1e-9);
+    }
+
+    let mut buf = reset_screen();
+    let mut zbuf = reset_zbuf();
+    let cube = rotate_triangles(cube, -0.5, 0.6, 0.0);
+    draw_3d_from_triangles(&mut buf, &mut zbuf, &Camera::default(), &Lighting::unlit(), &Culling::default(), cube);
+    assert_golden("get_box_triangles", &buf, EXACT);
+}
+
+#[test]
+fn lighting_under_non_uniform_scale() {
+    // a sphere squashed into an ellipsoid should be lit like one, the normal
+    // at q on it points along (q.x / sx², q.y / sy², q.z / sz²)
+    let s = V3::new(3.0, 0.5, 1.0);
+    let sun = Lighting {
+        lights: vec![Light::Directional {direction: V3::new(-1.0, -1.0, 0.0).normalize(), color: V3::new(1.0, 1.0, 1.0)}],
+        mode: ShadeMode::Gouraud,
+    };
+    let lambert = |n: V3| sun.shade_parts(&Material::matte(0xFFFFFF), V3::ZERO, n, n).0.x;
+    let mut off_if_stretched = 0.0f64;
+    for tri in transform_triangles(primitives::sphere(10.0, 16, 12, WHITE), &Mat4::scale(s)) {
+        for (q, n) in [(tri.v0, tri.n0), (tri.v1, tri.n1), (tri.v2, tri.n2)] {
+            let want = V3::new(q.x / (s.x * s.x), q.y / (s.y * s.y), q.z / (s.z * s.z)).normalize();
+            assert!((n - want).length() < 1e-9, "{:?} at {:?}, expected {:?}", n, q, want);
+            assert!((lambert(n) - lambert(want)).abs() < 1e-9);
+            // just scaling the normals like the points gets the light wrong
+            let stretched = V3::new(want.x * s.x * s.x, want.y * s.y * s.y, want.z * s.z * s.z).normalize();
+            off_if_stretched = off_if_stretched.max((lambert(stretched) - lambert(want)).abs());
+        }
+    }
+    assert!(off_if_stretched > 0.2, "{}", off_if_stretched);
+}
+
+#[test]
+fn golden_primitives() {
+    // one of each on a floor, smooth shaded so the normals show
+    let place = |tris: Vec<Triangle3d>, x: f64, y: f64, z: f64| {
+        transform_triangles(tris, &Mat4::translate(V3::new(x, y, z)))
+    };
+    let mut scene = place(primitives::plane(260.0, 160.0, 4, 2, WHITE), 0.0, -40.0, 40.0);
+    scene.extend(place(primitives::sphere(22.0, 16, 12, RED), -75.0, 25.0, 0.0));
+    scene.extend(place(primitives::cylinder(18.0, 45.0, 16, true, GREEN), 0.0, 25.0, 0.0));
+    scene.extend(place(primitives::cone(20.0, 45.0, 16, BLUE), 75.0, 25.0, 0.0));
+    scene.extend(place(rotate_triangles(primitives::torus(22.0, 7.0, 24, 10, YELLOW), -1.2, 0.0, 0.0), -50.0, -20.0, -20.0));
+    scene.extend(place(rotate_triangles(primitives::capsule(10.0, 35.0, 16, 6, WHITE), 0.0, 0.0, 1.3), 50.0, -20.0, -20.0));
+
+    let mut buf = reset_screen();
+    let mut zbuf = reset_zbuf();
+    let mut camera = Camera::default();
+    camera.orbit(0.0, -0.4); // looking down a bit, the floor is one sided
+    draw_3d_from_triangles(&mut buf, &mut zbuf, &camera, &Lighting::studio(ShadeMode::Phong), &Culling::default(), scene);
+    assert_golden("primitives", &buf, EXACT);
+}
+
+#[test]
+fn golden_textured() {
+    // a checkered floor going off into the distance, the lines stay straight
+    // and the squares shrink the way they should only if the uvs are
+    // interpolated with perspective, on it a cube with a 4x4 image stretched
+    // over each face with bilinear filtering so the texels blur into each other
+    let checker = Arc::new(texture::Texture::checker(64, 8, WHITE, 0x404040));
+    let mut floor = primitives::plane(300.0, 400.0, 1, 1, WHITE);
+    for tri in &mut floor {
+        (tri.uv0, tri.uv1, tri.uv2) = (tri.uv0 * 4.0, tri.uv1 * 4.0, tri.uv2 * 4.0);
+        tri.material.texture = Some(checker.clone());
+        tri.material.sampler.filter = texture::Filter::Nearest;
+    }
+    let mut scene = transform_triangles(floor, &Mat4::translate(V3::new(0.0, -40.0, 150.0)));
+
+    let colors = [RED, GREEN, BLUE, YELLOW, WHITE, BLACK, RED, GREEN, BLUE, YELLOW, WHITE, BLACK, RED, GREEN, BLUE, YELLOW];
+    let tiles = Arc::new(texture::Texture::new(4, 4, &colors));
+    let mut cube = rotate_triangles(get_box_triangles(V3::ZERO, V3::new(25.0, 25.0, 25.0), [WHITE; 6]), -0.5, 0.6, 0.0);
+    for tri in &mut cube {
+        tri.material.texture = Some(tiles.clone());
+        tri.material.sampler.wrap = texture::Wrap::Clamp;
+    }
+    scene.extend(transform_triangles(cube, &Mat4::translate(V3::new(0.0, 0.0, 50.0))));
+
+    let mut buf = reset_screen();
+    let mut zbuf = reset_zbuf();
+    let mut camera = Camera::default();
+    camera.orbit(0.0, -0.3);
+    draw_3d_from_triangles(&mut buf, &mut zbuf, &camera, &Lighting::unlit(), &Culling::default(), scene);
+    assert_golden("textured", &buf, EXACT);
+
+    // a texture on a plain white material is just the texture
+    let white = Material {texture: Some(checker), ..Material::matte(WHITE)};
+    assert_eq!(white.albedo(V2::new(0.01, 0.99), V2::ZERO, V2::ZERO), V3::new(1.0, 1.0, 1.0));
+}
+
+#[test]
+fn golden_mipmaps() {
+    // three strips of a fine checker floor running off to the horizon:
+    // bilinear on the left breaks up into moire in the distance, trilinear in
+    // the middle fades to gray instead, and anisotropic on the right keeps
+    // the squares further back before it does
+    let checker = Arc::new(texture::Texture::checker(64, 8, WHITE, 0x202020));
+    let strip = |x: f64, sampler: texture::Sampler| {
+        let mut floor = primitives::plane(90.0, 2000.0, 1, 1, WHITE);
+        for tri in &mut floor {
+            (tri.uv0, tri.uv1, tri.uv2) = (tri.uv0 * 3.0, tri.uv1 * 3.0, tri.uv2 * 3.0);
+            for uv in [&mut tri.uv0, &mut tri.uv1, &mut tri.uv2] {
+                uv.y *= 2000.0 / 90.0;
+            }
+            tri.material.texture = Some(checker.clone());
+            tri.material.sampler = sampler;
+        }
+        transform_triangles(floor, &Mat4::translate(V3::new(x, -40.0, 900.0)))
+    };
+    let trilinear = texture::Sampler::default();
+    let mut scene = strip(-95.0, texture::Sampler {filter: texture::Filter::Bilinear, ..trilinear});
+    scene.extend(strip(0.0, trilinear));
+    scene.extend(strip(95.0, texture::Sampler {anisotropy: 16, ..trilinear}));
+
+    let mut buf = reset_screen();
+    let mut zbuf = reset_zbuf();
+    let mut camera = Camera::default();
+    camera.orbit(0.0, -0.1);
+    draw_3d_from_triangles(&mut buf, &mut zbuf, &camera, &Lighting::unlit(), &Culling::default(), scene);
+    assert_golden("mipmaps", &buf, EXACT);
+}
@@ -0,0 +1,239 @@
+// lights, materials and the shading math
+// diffuse (lambert): a surface is as bright as the cosine of the angle
+// between its normal and the direction to the light, so it's brightest
+// facing the light head on and black from 90 degrees on
+// specular (blinn-phong): the highlight, strongest where the normal lines up
+// with the half way vector between the light and the eye, and `shininess`
+// says how quickly it falls off, big numbers make small sharp highlights
+//
+// colors here are V3s with r, g, b in 0..1 so they can be added and scaled,
+// they only turn back into 0xRRGGBB right before going into the buffer
+
+use std::sync::Arc;
+
+use crate::math::{V2, V3};
+use crate::texture::{Sampler, Texture};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Light {
+    // lights everything the same from everywhere, so the shadow side isn't pitch black
+    Ambient {color: V3},
+    // the sun, infinitely far away, `direction` is the way the light travels
+    Directional {direction: V3, color: V3},
+    // a bulb, gets dimmer with distance d as 1 / (constant + linear d + quadratic d^2)
+    Point {position: V3, color: V3, attenuation: (f64, f64, f64)},
+}
+
+// where in the pipeline the lighting happens
+//   Unlit   - the triangle's color as is, no lights at all
+//   Flat    - once per triangle with the face normal, faceted look
+//   Gouraud - once per vertex with the vertex normals, the colors are blended
+//             across the triangle so curved things look smooth
+//   Phong   - once per pixel, the normals and view vectors are blended instead
+//             and blinn-phong runs on those, so highlights land mid-triangle
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ShadeMode {Unlit, Flat, Gouraud, Phong}
+
+impl ShadeMode {
+    pub fn parse(s: &str) -> Option<ShadeMode> {
+        match s {
+            "unlit" => Some(ShadeMode::Unlit),
+            "flat" => Some(ShadeMode::Flat),
+            "gouraud" => Some(ShadeMode::Gouraud),
+            "phong" => Some(ShadeMode::Phong),
+            _ => None,
+        }
+    }
+}
+
+// what a surface does with the light that hits it, colors in 0..1
+// a texture gets multiplied into the diffuse color wherever the triangle's
+// uvs say, the Arc is so every triangle of a part can share one image
+#[derive(Clone, PartialEq, Debug)]
+pub struct Material {
+    pub diffuse: V3,
+    pub specular: V3,
+    pub shininess: f64,
+    pub emissive: V3, // glows on its own, even with no lights at all
+    pub texture: Option<Arc<Texture>>,
+    pub sampler: Sampler,
+}
+
+impl Material {
+    // no highlight, what every triangle gets unless somebody says otherwise
+    pub fn matte(color: u32) -> Material {
+        Material {
+            diffuse: color_to_v3(color),
+            specular: V3::ZERO,
+            shininess: 1.0,
+            emissive: V3::ZERO,
+            texture: None,
+            sampler: Sampler::default(),
+        }
+    }
+
+    // a white highlight on top of the color, like a plastic toy
+    pub fn plastic(color: u32) -> Material {
+        Material {specular: V3::new(0.5, 0.5, 0.5), shininess: 32.0, ..Material::matte(color)}
+    }
+
+    // the surface color at a spot on the triangle, `dx` and `dy` are how
+    // the uvs change from pixel to pixel there (see Texture::sample)
+    pub fn albedo(&self, uv: V2, dx: V2, dy: V2) -> V3 {
+        match &self.texture {
+            Some(texture) => modulate(self.diffuse, texture.sample(uv, dx, dy, self.sampler)),
+            None => self.diffuse,
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material::matte(0xFFFFFF)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Lighting {
+    pub lights: Vec<Light>,
+    pub mode: ShadeMode,
+}
+
+impl Lighting {
+    #[cfg(test)]
+    pub fn unlit() -> Lighting {
+        Lighting {lights: vec![], mode: ShadeMode::Unlit}
+    }
+
+    // a dim fill, a key light from the upper left in front and a warm bulb
+    // off to the right, enough to see the shape of things
+    pub fn studio(mode: ShadeMode) -> Lighting {
+        Lighting {
+            lights: vec![
+                Light::Ambient {color: V3::new(0.15, 0.15, 0.15)},
+                Light::Directional {direction: V3::new(1.0, -1.0, 1.0).normalize(), color: V3::new(0.8, 0.8, 0.8)},
+                Light::Point {position: V3::new(120.0, 40.0, -80.0), color: V3::new(0.6, 0.5, 0.4), attenuation: (1.0, 0.0, 0.00005)},
+            ],
+            mode,
+        }
+    }
+
+    // the color of a point on a surface comes in two halves so a texture can
+    // go in between: the light that lands on the surface, which still has to
+    // be multiplied by its color, and the highlight plus emissive that go on
+    // top as they are
+    // `view` points from the surface to the eye and like the normal it has to
+    // be normalized
+    pub fn shade_parts(&self, material: &Material, position: V3, normal: V3, view: V3) -> (V3, V3) {
+        let (mut light, mut glow) = (V3::ZERO, material.emissive);
+        for l in &self.lights {
+            let Some((to_light, color)) = incoming(l, position) else {
+                light += light_color(l);
+                continue;
+            };
+            let lambert = normal.dot(to_light);
+            if lambert <= 0.0 {
+                continue; // the light is behind the surface, no highlight either
+            }
+            let half = (to_light + view).normalize();
+            let spec = normal.dot(half).max(0.0).powf(material.shininess);
+            light += color * lambert;
+            glow += modulate(material.specular, color) * spec;
+        }
+        (light, glow)
+    }
+}
+
+fn light_color(light: &Light) -> V3 {
+    match *light {
+        Light::Ambient {color} | Light::Directional {color, ..} | Light::Point {color, ..} => color,
+    }
+}
+
+// which way the light comes from (normalized, pointing at the light) and how
+// much of it is left when it gets to `position`, None for ambient light
+fn incoming(light: &Light, position: V3) -> Option<(V3, V3)> {
+    match *light {
+        Light::Ambient {..} => None,
+        Light::Directional {direction, color} => Some((-direction.normalize(), color)),
+        Light::Point {position: at, color, attenuation: (c, l, q)} => {
+            let to_light = at - position;
+            let d = to_light.length();
+            Some((to_light.normalize(), color / (c + l * d + q * d * d)))
+        }
+    }
+}
+
+pub fn color_to_v3(color: u32) -> V3 {
+    V3::new(
+        ((color >> 16) & 0xFF) as f64 / 255.0,
+        ((color >> 8) & 0xFF) as f64 / 255.0,
+        (color & 0xFF) as f64 / 255.0,
+    )
+}
+
+// anything over 1 just saturates
+pub fn v3_to_color(c: V3) -> u32 {
+    let channel = |v: f64| (v.clamp(0.0, 1.0) * 255.0).round() as u32;
+    (channel(c.x) << 16) | (channel(c.y) << 8) | channel(c.z)
+}
+
+// a base color lit by some amount of light, channel by channel
+pub fn modulate(base: V3, light: V3) -> V3 {
+    V3::new(base.x * light.x, base.y * light.y, base.z * light.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // just the light landing on a dull surface facing `normal`
+    fn diffuse(lighting: &Lighting, normal: V3) -> V3 {
+        lighting.shade_parts(&Material::plastic(0x000000), V3::ZERO, normal, normal).0
+    }
+
+    #[test]
+    fn lambert_falls_off_with_angle_and_distance() {
+        let up = V3::new(0.0, 1.0, 0.0);
+        let sun = Lighting {
+            lights: vec![Light::Directional {direction: V3::new(0.0, -1.0, 0.0), color: V3::new(1.0, 1.0, 1.0)}],
+            mode: ShadeMode::Flat,
+        };
+        assert!((diffuse(&sun, up).x - 1.0).abs() < 1e-9);
+        let tilted = V3::new(1.0, 1.0, 0.0).normalize();
+        assert!((diffuse(&sun, tilted).x - 0.5f64.sqrt()).abs() < 1e-9);
+        assert_eq!(diffuse(&sun, -up).x, 0.0);
+
+        let bulb = Lighting {
+            lights: vec![Light::Point {position: V3::new(0.0, 10.0, 0.0), color: V3::new(1.0, 1.0, 1.0), attenuation: (0.0, 0.0, 1.0)}],
+            mode: ShadeMode::Flat,
+        };
+        assert!((diffuse(&bulb, up).x - 0.01).abs() < 1e-9);
+    }
+
+    #[test]
+    fn blinn_phong_highlight() {
+        let up = V3::new(0.0, 1.0, 0.0);
+        let sun = Lighting {
+            lights: vec![Light::Directional {direction: -up, color: V3::new(1.0, 1.0, 1.0)}],
+            mode: ShadeMode::Phong,
+        };
+        let shiny = Material {emissive: V3::new(0.1, 0.0, 0.0), ..Material::plastic(0x000000)};
+        // looking straight down the reflection, the highlight is at full strength
+        assert!((sun.shade_parts(&shiny, V3::ZERO, up, up).1.y - 0.5).abs() < 1e-9);
+        // off to the side it's much weaker, and the emissive is always there
+        let side = sun.shade_parts(&shiny, V3::ZERO, up, V3::new(1.0, 1.0, 0.0).normalize()).1;
+        assert!(side.y > 0.0 && side.y < 0.1);
+        assert!((side.x - side.y - 0.1).abs() < 1e-9);
+        // lit from behind, only the glow is left
+        assert_eq!(sun.shade_parts(&shiny, V3::ZERO, -up, -up), (V3::ZERO, V3::new(0.1, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn color_round_trip() {
+        for c in [0x000000, 0xFFFFFF, 0x12AB7F] {
+            assert_eq!(v3_to_color(color_to_v3(c)), c);
+        }
+        assert_eq!(v3_to_color(V3::new(2.0, -1.0, 0.5)), 0xFF0080);
+    }
+}
@@ -1,55 +1,127 @@
+// the 2D/3D names are on purpose, so don't let the compiler nag about them
+#![allow(non_snake_case)]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use minifb::{Key, Window, WindowOptions};
 
+mod anim;
+mod camera;
+mod clip;
+mod cull;
+mod gltf;
+mod light;
+mod math;
+mod mesh;
+mod model;
+mod obj;
+mod output;
+mod ply;
+mod primitives;
+mod quat;
+mod scene;
+mod skeleton;
+mod stl;
+mod texture;
+mod timing;
+
+#[cfg(test)]
+mod golden;
+
+use clip::{clip_polygon, clip_triangle, to_screen, ClipVertex, ALL_PLANES, DEPTH_PLANES};
+use cull::{Culling, BACK_FACE_COLOR};
+use light::{modulate, v3_to_color, Lighting, Material, ShadeMode};
+use math::{Mat4, V2, V3, V4};
+use quat::Quat;
+use mesh::Mesh;
+use anim::{Clip, Interp, PlayMode};
+use camera::Camera;
+use skeleton::{Pose, Skeleton};
+use texture::{Filter, Texture, Wrap, MAX_ANISOTROPY};
+use timing::{FixedStep, FrameClock};
+
 const WIDTH: usize = 800;
 const HEIGHT: usize = 800;
 const FOV: f64 = 90.0f64.to_radians();
 const ASPECT_RATIO: f64 = WIDTH as f64 / HEIGHT as f64; 
+const NEAR: f64 = 0.1;
+const FAR: f64 = 1000.0;
 
 // Colors so I don't go insane type hexcodes
+// the ones only the golden tests draw with stay out of the real build
+#[cfg(test)]
 const BLACK: u32 = 0x000000;
 const WHITE: u32 = 0xFFFFFF;
+#[cfg(test)]
 const RED:   u32 = 0xFF0000;
 const GREEN: u32 = 0x00FF00;
+#[cfg(test)]
 const BLUE:  u32 = 0x0000FF;
+#[cfg(test)]
 const YELLOW:u32 = 0xFFFF00;
 
 
 // some 3D structs, I don't know if doing it this way is smart
-// but I will probably put this in a different file later anyway
+// V3 lives in math.rs now with the rest of the vector stuff
 // everything is a triangle so it doesn't matter if this is it lol
-#[derive(Clone, Copy)]
-struct V3 {x: f64, y: f64, z: f64}
-#[derive(Clone, Copy)]
-struct Triangle3d {v0: V3, v1: V3, v2: V3, color: u32}
+// n0..n2 are the normals at each corner, the face normal for a hard edged
+// thing like a cuboid, or averaged with the neighbours to look round
+// color is what unlit drawing and the wireframe use, the lights look at the material
+// uv0..uv2 say which spot of the material's texture each corner gets
+#[derive(Clone, Debug)]
+struct Triangle3d {v0: V3, v1: V3, v2: V3, color: u32, n0: V3, n1: V3, n2: V3, uv0: V2, uv1: V2, uv2: V2, material: Material}
+
+impl Triangle3d {
+    // keeps the flat color in line with the material
+    fn set_material(&mut self, material: Material) {
+        self.color = v3_to_color(material.diffuse);
+        self.material = material;
+    }
+
+    // v0 -> v1 -> v2 clockwise when looking at it gives a normal pointing at you
+    fn face_normal(&self) -> V3 {
+        (self.v1 - self.v0).cross(self.v2 - self.v0).normalize()
+    }
+
+    fn center(&self) -> V3 {
+        (self.v0 + self.v1 + self.v2) / 3.0
+    }
+}
 
 fn reset_screen() -> Vec<u32> {
-    return vec![0; WIDTH * HEIGHT];
+    vec![0; WIDTH * HEIGHT]
+}
+
+// depth buffer that lives next to the color buffer, same trick as the
+// spinning cube: store 1/z so bigger means closer
+fn reset_zbuf() -> Vec<f64> {
+    vec![f64::NEG_INFINITY; WIDTH * HEIGHT]
 }
 
 // project 3D coordinates to 2D coordinates, this is probably
 // the backbone of all 3D stuff here
-fn project_3D_to_2D(mut v: V3) -> (i32, i32) {
-    // Move camera backwards instead of pushing objects forward
-    let camera_z = -150.0;
-    v.z -= camera_z;
+// None when the point is outside the near/far range, a single point can't be
+// clipped so there is nothing better to hand back
+#[cfg(test)]
+fn project_3D_to_2D(camera: &Camera, v: V3) -> Option<(i32, i32)> {
+    let (x_screen, y_screen, _) = project_3D(camera, v)?;
+    Some((x_screen as i32, y_screen as i32))
+}
 
-    if v.z <= 0.01 {
-        return (WIDTH as i32 / 2, HEIGHT as i32 / 2); // clipped behind camera
+// same as above but keeps the sub-pixel position and the depth around,
+// the depth comes back as 1/z because that is what interpolates linearly
+// across the screen (z itself doesn't after the perspective divide)
+fn project_3D(camera: &Camera, v: V3) -> Option<(f64, f64, f64)> {
+    let p = camera.world_to_clip(v);
+    if !clip::is_inside(p, &DEPTH_PLANES) {
+        return None;
     }
-
-    let scale = 1.0 / (FOV * 0.5).tan();
-    let aspect_ratio = WIDTH as f64 / HEIGHT as f64;
-
-    let x_ndc = (v.x * scale) / (v.z * aspect_ratio); // normalized -1..1
-    let y_ndc = -(v.y * scale) / v.z;
-
-    let x_screen = (x_ndc * WIDTH as f64 / 2.0) + WIDTH as f64 / 2.0;
-    let y_screen = (y_ndc * HEIGHT as f64 / 2.0) + HEIGHT as f64 / 2.0;
-
-    (x_screen as i32, y_screen as i32)
+    Some(to_screen(p))
 }
 
 // fairly standard way to make a line, just step through
+#[cfg(test)]
 fn make_line(buf: &mut [u32], p1_x: usize, p1_y: usize, p2_x: usize, p2_y: usize, color: u32) {
     let x0 = p1_x as isize;
     let y0 = p1_y as isize;
@@ -82,6 +154,92 @@ fn make_line(buf: &mut [u32], p1_x: usize, p1_y: usize, p2_x: usize, p2_y: usize
     }
 }
 
+// how make_line_styled draws: plain lines are solid pixels like make_line,
+// smooth ones are Xiaolin Wu's anti-aliased lines, every pixel gets the color
+// blended in by how much of it the line covers, so the stair steps turn into
+// soft edges
+// width is across the line in pixels, for both kinds
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct LineStyle {smooth: bool, width: f64}
+
+impl LineStyle {
+    const PLAIN: LineStyle = LineStyle {smooth: false, width: 1.0};
+    const SMOOTH: LineStyle = LineStyle {smooth: true, width: 1.0};
+}
+
+impl Default for LineStyle {
+    fn default() -> LineStyle {
+        LineStyle::PLAIN
+    }
+}
+
+// mixes `color` over what's in the buffer, coverage 0 leaves it and 1 replaces it
+fn blend_pixel(buf: &mut [u32], x: isize, y: isize, color: u32, coverage: f64) {
+    if x < 0 || x >= WIDTH as isize || y < 0 || y >= HEIGHT as isize || coverage <= 0.0 {
+        return;
+    }
+    let idx = y as usize * WIDTH + x as usize;
+    let a = coverage.min(1.0);
+    let mix = |shift: u32| {
+        let (old, new) = ((buf[idx] >> shift & 0xFF) as f64, (color >> shift & 0xFF) as f64);
+        ((old + (new - old) * a).round() as u32) << shift
+    };
+    buf[idx] = mix(16) | mix(8) | mix(0);
+}
+
+// a line between points with sub pixel positions, (x, y) is in pixel
+// floor(x), floor(y) like the fills have it
+// it walks the long axis one pixel at a time like make_line, and at each
+// step works out where the line's middle is on the short axis, the pixels
+// there get how much of them the line's span covers: the line's width
+// stretched by the slope since it's measured straight across
+// smooth with width 1 that's Wu's two pixels sharing the intensity by how
+// close the middle is to each, and the end pixels only getting the part the
+// line actually reaches
+// plain lines fill the pixels whose middle is inside the span instead, and
+// at least the one nearest the middle so thin ones don't break up
+fn make_line_styled(buf: &mut [u32], x0: f64, y0: f64, x1: f64, y1: f64, color: u32, style: LineStyle) {
+    // pixel middles on whole numbers from here on
+    let (x0, y0, x1, y1) = (x0 - 0.5, y0 - 0.5, x1 - 0.5, y1 - 0.5);
+    let steep = (y1 - y0).abs() > (x1 - x0).abs();
+    // along is the long axis, across the short one
+    let (a0, c0, a1, c1) = if steep {(y0, x0, y1, x1)} else {(x0, y0, x1, y1)};
+    let (a0, c0, a1, c1) = if a0 > a1 {(a1, c1, a0, c0)} else {(a0, c0, a1, c1)};
+    if !(a0.is_finite() && a1.is_finite() && c0.is_finite() && c1.is_finite()) {
+        return;
+    }
+
+    let slope = if a1 > a0 {(c1 - c0) / (a1 - a0)} else {0.0};
+    let half = style.width / 2.0 * (1.0 + slope * slope).sqrt();
+    let mut plot = |a: isize, c: isize, coverage: f64| {
+        if steep {blend_pixel(buf, c, a, color, coverage)} else {blend_pixel(buf, a, c, color, coverage)}
+    };
+
+    // nothing on screen is more than this far along, no use walking past it
+    let limit = WIDTH.max(HEIGHT) as f64 + half + 1.0;
+    let (first, last) = (a0.round().max(-1.0), a1.round().min(limit));
+    let mut a = first;
+    while a <= last {
+        let middle = c0 + slope * (a - a0);
+        let (lo, hi) = (middle - half, middle + half);
+        // the part of this column the line reaches, all of it except at the ends
+        let along = if style.smooth {((a + 0.5).min(a1) - (a - 0.5).max(a0)).clamp(0.0, 1.0)} else {1.0};
+        let mut c = (lo - 0.5).floor();
+        while c <= (hi + 0.5).ceil() {
+            let coverage = if style.smooth {
+                ((c + 0.5).min(hi) - (c - 0.5).max(lo)).clamp(0.0, 1.0)
+            } else if (c >= lo && c < hi) || c == middle.round() {
+                1.0
+            } else {
+                0.0
+            };
+            plot(a as isize, c as isize, coverage * along);
+            c += 1.0;
+        }
+        a += 1.0;
+    }
+}
+
 // another potential way to do this is to just make a lot
 // of lines but this is easier
 fn make_square_filled(buf: &mut [u32], cx: usize, cy: usize, size: i32, color: u32){
@@ -98,6 +256,7 @@ fn make_square_filled(buf: &mut [u32], cx: usize, cy: usize, size: i32, color: u
 }
 
 // basic square with 4 lines
+#[cfg(test)]
 fn make_square(buf: &mut [u32], cx: usize, cy: usize, size: i32, color: u32){
     let c1_x = cx - (size / 2) as usize; 
     let c1_y = cy - (size / 2) as usize;
@@ -110,48 +269,162 @@ fn make_square(buf: &mut [u32], cx: usize, cy: usize, size: i32, color: u32){
     make_line(buf, c2_x, c2_y, c2_x, c1_y, color); 
 }
 
-// another basic shape for 2D
-fn make_triangle_2D(buf: &mut [u32], v1_x: usize, v1_y: usize, v2_x: usize, v2_y: usize, v3_x: usize, v3_y: usize, color: u32) {
-    make_line(buf, v1_x, v1_y, v3_x, v3_y, color); 
-    make_line(buf, v2_x, v2_y, v3_x, v3_y, color);
-    make_line(buf, v2_x, v2_y, v1_x, v1_y, color);
+// the other backbone of all 3D, the best primitive
+// solid now, the z-buffer sorts out which cuboid is in front
+// the triangle gets cut against the frustum first, a triangle poking behind
+// the camera comes out as one or two smaller ones that are fine to draw
+// culling comes first, a triangle facing the wrong way doesn't even get projected
+// lighting happens here in world space, flat once for the whole triangle,
+// gouraud once per corner with the lit colors riding through the clipper,
+// or phong once per pixel from the normals and view vectors riding along instead
+// textures are looked up per pixel whatever the mode, the uvs ride along too and
+// the fill's weights are perspective correct so the image doesn't swim
+// `to_clip` is the camera's view_projection, worked out once for the whole draw
+fn make_triangle_3D(buf: &mut [u32], zbuf: &mut [f64], camera: &Camera, to_clip: &Mat4, lighting: &Lighting, culling: &Culling, triangle: Triangle3d) {
+    let face = triangle.face_normal();
+    let front = culling.is_front(face, triangle.center(), camera.position);
+    if !culling.keep(front) {
+        return;
+    }
+
+    let c0 = to_clip.transform_v4(triangle.v0);
+    let c1 = to_clip.transform_v4(triangle.v1);
+    let c2 = to_clip.transform_v4(triangle.v2);
+
+    let debug_back = !front && culling.show_back_faces;
+    let textured = triangle.material.texture.is_some();
+    if (lighting.mode == ShadeMode::Unlit && !textured) || debug_back {
+        let color = if debug_back {BACK_FACE_COLOR} else {triangle.color};
+        for [a, b, c] in clip_triangle(c0, c1, c2, &ALL_PLANES) {
+            fill_triangle_3D(buf, zbuf, to_screen(a), to_screen(b), to_screen(c), color);
+        }
+        return;
+    }
+
+    // light whichever side we're looking at, with culling off the back of a
+    // triangle gets lit like a front
+    let flip = if face.dot(camera.position - triangle.center()) < 0.0 {-1.0} else {1.0};
+    let material = &triangle.material;
+    let lit = |p: V3, n: V3| lighting.shade_parts(material, p, n * flip, (camera.position - p).normalize());
+
+    let corner = |clip: V4, world: V3, normal: V3, uv: V2| {
+        let (light, glow) = match lighting.mode {
+            ShadeMode::Gouraud => lit(world, normal),
+            _ => (V3::ZERO, V3::ZERO),
+        };
+        ShadedVertex {clip, light, glow, world, normal: normal * flip, view: camera.position - world, uv}
+    };
+    let v0 = corner(c0, triangle.v0, triangle.n0, triangle.uv0);
+    let v1 = corner(c1, triangle.v1, triangle.n1, triangle.uv1);
+    let v2 = corner(c2, triangle.v2, triangle.n2, triangle.uv2);
+    let (flat_light, flat_glow) = lit(triangle.center(), face);
+    let flat = v3_to_color(modulate(material.diffuse, flat_light) + flat_glow);
+
+    for [a, b, c] in clip_triangle(v0, v1, v2, &ALL_PLANES) {
+        let p = [to_screen(a.clip), to_screen(b.clip), to_screen(c.clip)];
+        let blend = |f: fn(&ShadedVertex) -> V3, w: [f64; 3]| f(&a) * w[0] + f(&b) * w[1] + f(&c) * w[2];
+        // the surface color under the pixel, only a texture makes it change
+        // uv is linear in the weights so their differences give the uv's too
+        // (only trilinear asks for those, the rest make do with the uv alone)
+        let uv = |w: [f64; 3]| a.uv * w[0] + b.uv * w[1] + c.uv * w[2];
+        let albedo = |f: Frag| match material.texture {
+            None => material.diffuse,
+            Some(_) if material.sampler.filter == Filter::Trilinear => material.albedo(uv(f.w), uv(f.dx()), uv(f.dy())),
+            Some(_) => material.albedo(uv(f.w), V2::ZERO, V2::ZERO),
+        };
+        match lighting.mode {
+            ShadeMode::Unlit => fill_triangle_3D_with(buf, zbuf, p, |f| v3_to_color(albedo(f))),
+            ShadeMode::Flat if !textured => fill_triangle_3D_with(buf, zbuf, p, |_| flat),
+            ShadeMode::Flat => fill_triangle_3D_with(buf, zbuf, p, |f| v3_to_color(modulate(albedo(f), flat_light) + flat_glow)),
+            ShadeMode::Gouraud => fill_triangle_3D_with(buf, zbuf, p, |f| {
+                v3_to_color(modulate(albedo(f), blend(|v| v.light, f.w)) + blend(|v| v.glow, f.w))
+            }),
+            ShadeMode::Phong => fill_triangle_3D_with(buf, zbuf, p, |f| {
+                let normal = blend(|v| v.normal, f.w).normalize();
+                let view = blend(|v| v.view, f.w).normalize();
+                let (light, glow) = lighting.shade_parts(material, blend(|v| v.world, f.w), normal, view);
+                v3_to_color(modulate(albedo(f), light) + glow)
+            }),
+        }
+    }
 }
 
-// the other backbone of all 3D, the best primitive
-fn make_triangle_3D(buf: &mut [u32], triangle: Triangle3d) {
-    let (x0, y0) = project_3D_to_2D(triangle.v0);
-    let (x1, y1) = project_3D_to_2D(triangle.v1);
-    let (x2, y2) = project_3D_to_2D(triangle.v2);
-    
-    make_triangle_2D(
-        buf,
-        x0 as usize, y0 as usize,
-        x1 as usize, y1 as usize,
-        x2 as usize, y2 as usize,
-        triangle.color,
-    );
-    // fill_triangle(buf, x0, y0, x1, y1, x2, y2, triangle.color);
+// a clip space corner and everything the shading needs from it, the clipper
+// blends all of it wherever it has to cut an edge
+// light and glow are the gouraud lighting (see Lighting::shade_parts), world,
+// normal and view are for lighting per pixel, uv is where the texture goes
+#[derive(Clone, Copy)]
+struct ShadedVertex {clip: V4, light: V3, glow: V3, world: V3, normal: V3, view: V3, uv: V2}
+
+impl ClipVertex for ShadedVertex {
+    fn clip_pos(&self) -> V4 {
+        self.clip
+    }
+
+    fn lerp(self, o: ShadedVertex, t: f64) -> ShadedVertex {
+        ShadedVertex {
+            clip: self.clip.lerp(o.clip, t),
+            light: self.light.lerp(o.light, t),
+            glow: self.glow.lerp(o.glow, t),
+            world: self.world.lerp(o.world, t),
+            normal: self.normal.lerp(o.normal, t),
+            view: self.view.lerp(o.view, t),
+            uv: self.uv.lerp(o.uv, t),
+        }
+    }
 }
 
-// unused right now, might not need it at all lol
-fn rotate_y(v: V3, an: f64) -> V3 {
-    let sin_a = an.sin();
-    let cos_a = an.cos();
-    V3 {
-        x: v.x * cos_a - v.z * sin_a,
-        y: v.y,
-        z: v.x * sin_a + v.z * cos_a,
+// the old see-through version, still handy for debugging
+// only the near/far planes cut it so we don't get extra lines along the
+// screen border, the line drawing already ignores pixels off screen
+fn make_triangle_3D_wireframe(buf: &mut [u32], to_clip: &Mat4, triangle: Triangle3d, style: LineStyle) {
+    let c0 = to_clip.transform_v4(triangle.v0);
+    let c1 = to_clip.transform_v4(triangle.v1);
+    let c2 = to_clip.transform_v4(triangle.v2);
+
+    let poly = clip_polygon(vec![c0, c1, c2], &DEPTH_PLANES);
+    for i in 0..poly.len() {
+        let (x0, y0, _) = to_screen(poly[i]);
+        let (x1, y1, _) = to_screen(poly[(i + 1) % poly.len()]);
+        make_line_styled(buf, x0, y0, x1, y1, triangle.color, style);
     }
 }
 
 // helper to make a Triangle struct
+// every corner gets the face normal, smooth_normals can round it off later
 fn get_triangle_from_vecs(v0: V3, v1: V3, v2: V3, color: u32) -> Triangle3d {
-    return Triangle3d {
-        v0: v0, 
-        v1: v1, 
-        v2: v2,
-        color: color, 
-    }; 
+    let n = (v1 - v0).cross(v2 - v0).normalize();
+    Triangle3d {
+        v0,
+        v1,
+        v2,
+        color,
+        n0: n,
+        n1: n,
+        n2: n,
+        uv0: V2::new(0.0, 0.0),
+        uv1: V2::new(0.0, 0.0),
+        uv2: V2::new(0.0, 0.0),
+        material: Material::matte(color),
+    }
+}
+
+// vertex normals: every corner gets the average of the faces that share its
+// position, bigger faces count more (the cross product isn't normalized)
+fn smooth_normals(triangles: &mut [Triangle3d]) {
+    let key = |v: V3| (v.x.to_bits(), v.y.to_bits(), v.z.to_bits());
+    let mut sums: HashMap<(u64, u64, u64), V3> = HashMap::new();
+    for tri in triangles.iter() {
+        let n = (tri.v1 - tri.v0).cross(tri.v2 - tri.v0);
+        for v in [tri.v0, tri.v1, tri.v2] {
+            *sums.entry(key(v)).or_default() += n;
+        }
+    }
+    for tri in triangles.iter_mut() {
+        tri.n0 = sums[&key(tri.v0)].normalize();
+        tri.n1 = sums[&key(tri.v1)].normalize();
+        tri.n2 = sums[&key(tri.v2)].normalize();
+    }
 }
 
 // helper to get an edge
@@ -161,6 +434,8 @@ fn edge_function(ax: f64, ay: f64, bx: f64, by: f64, cx: f64, cy: f64) -> f64 {
 
 // making filled triangles
 // dont think real life is just wireframes lol
+#[allow(clippy::too_many_arguments)]
+#[cfg(test)]
 fn fill_triangle(buf: &mut [u32], x0: i32, y0: i32, 
                  x1: i32, y1: i32, 
                  x2: i32, y2: i32, color: u32) {
@@ -194,819 +469,679 @@ fn fill_triangle(buf: &mut [u32], x0: i32, y0: i32,
     }
 }
 
-// time to add lighting
-
-// Unlike the 2D shapes that I just draw straight to the buffer, my idea
-// with the 3D shapes is to create generators that output a list of triangles
-// that can be then projected one at a time onto the buffer
-// this will make doing manipulations like rotations, transforms, and translations easier
-// ...once I get there
-fn get_cube_triangles(size: i32, cx: usize, cy: usize, cz: usize, color: u32) -> Vec<Triangle3d> {
-    let c1_x = cx - (size ) as usize; 
-    let c1_y = cy - (size ) as usize;
-    let c1_z = cz - (size ) as usize;
-
-    let c2_x = cx + (size ) as usize; 
-    let c2_y = cy + (size ) as usize;
-    let c2_z = cz + (size ) as usize;
-
-    // binary iteration
-    // It's like the Klein-4 group but with three switches
-    let v_000 = V3 {x: c1_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    let v_001 = V3 {x: c1_x as f64, y: c1_y as f64, z: c2_z as f64};
-    let v_010 = V3 {x: c1_x as f64, y: c2_y as f64, z: c1_z as f64};
-    let v_100 = V3 {x: c2_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    let v_011 = V3 {x: c1_x as f64, y: c2_y as f64, z: c2_z as f64};
-    let v_110 = V3 {x: c2_x as f64, y: c2_y as f64, z: c1_z as f64};
-    let v_101 = V3 {x: c2_x as f64, y: c1_y as f64, z: c2_z as f64};
-
-    let v_111 = V3 {x: c2_x as f64, y: c2_y as f64, z: c2_z as f64};
-
-    return vec![
-    // triangles to make: 
-        get_triangle_from_vecs(v_000, v_001, v_010, color), // 000-001-010
-        get_triangle_from_vecs(v_000, v_001, v_100, color), // 000-001-100
-        get_triangle_from_vecs(v_000, v_010, v_100, color), // 000-010-100
-        
-        get_triangle_from_vecs(v_111, v_101, v_110, color), // 111-101-110
-        get_triangle_from_vecs(v_111, v_101, v_011, color), // 111-101-011
-        get_triangle_from_vecs(v_111, v_110, v_011, color), // 111-110-011
-
-        get_triangle_from_vecs(v_100, v_101, v_001, color), // 100-101-001
-        get_triangle_from_vecs(v_100, v_101, v_110, color), // 100-101-110
-        get_triangle_from_vecs(v_100, v_110, v_010, color), // 100-110-010
-
-        get_triangle_from_vecs(v_011, v_101, v_001, color), // 011-101-001
-        get_triangle_from_vecs(v_011, v_010, v_110, color), // 011-010-110
-        get_triangle_from_vecs(v_011, v_001, v_010, color), // 011-001-010
-
-    ];
+// fill_triangle but with depth: each point is (x, y, 1/z) straight out of
+// project_3D, the barycentric weights that decide coverage also blend the
+// three depths so every pixel gets its own z to test against the buffer
+fn fill_triangle_3D(buf: &mut [u32], zbuf: &mut [f64],
+                    p0: (f64, f64, f64),
+                    p1: (f64, f64, f64),
+                    p2: (f64, f64, f64), color: u32) {
+    fill_triangle_3D_with(buf, zbuf, [p0, p1, p2], |_| color);
 }
 
-// wireframe hand! 
-fn wireframe_hand(color1: u32) -> (Vec<Triangle3d>, Vec<(String, usize, usize)>) {
-    let mut out_vec = vec![]; 
-    let mut idx_tup = vec![]; 
-
-    // base of the hand
-    let mut size = 20.0;
-
-    let mut cx = 0.0; 
-    let mut cy = -30.0; 
-    let mut cz = -15.0;
-
-    let mut c1_x = cx - size;
-    let mut c1_y = cy - size / 4.0;
-    let mut c1_z = cz - size;
-
-    let mut c2_x = cx + size;
-    let mut c2_y = cy + size / 4.0;
-    let mut c2_z = cz + size;
-
-    // binary iteration
-    // It's like the Klein-4 group but with three switches
-    let mut v_000 = V3 {x: c1_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    let mut v_001 = V3 {x: c1_x as f64, y: c1_y as f64, z: c2_z as f64};
-    let mut v_010 = V3 {x: c1_x as f64, y: c2_y as f64, z: c1_z as f64};
-    let mut v_100 = V3 {x: c2_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    let mut v_011 = V3 {x: c1_x as f64, y: c2_y as f64, z: c2_z as f64};
-    let mut v_110 = V3 {x: c2_x as f64, y: c2_y as f64, z: c1_z as f64};
-    let mut v_101 = V3 {x: c2_x as f64, y: c1_y as f64, z: c2_z as f64};
-
-    let mut v_111 = V3 {x: c2_x as f64, y: c2_y as f64, z: c2_z as f64};
-
-    let mut color = RED; 
-    let mut base_vecs = vec![
-    // triangles to make: 
-        get_triangle_from_vecs(v_000, v_001, v_010, color), // 000-001-010
-        get_triangle_from_vecs(v_000, v_001, v_100, color), // 000-001-100
-        get_triangle_from_vecs(v_000, v_010, v_100, color), // 000-010-100
-        
-        get_triangle_from_vecs(v_111, v_101, v_110, color), // 111-101-110
-        get_triangle_from_vecs(v_111, v_101, v_011, color), // 111-101-011
-        get_triangle_from_vecs(v_111, v_110, v_011, color), // 111-110-011
-
-        get_triangle_from_vecs(v_100, v_101, v_001, color), // 100-101-001
-        get_triangle_from_vecs(v_100, v_101, v_110, color), // 100-101-110
-        get_triangle_from_vecs(v_100, v_110, v_010, color), // 100-110-010
-
-        get_triangle_from_vecs(v_011, v_101, v_001, color), // 011-101-001
-        get_triangle_from_vecs(v_011, v_010, v_110, color), // 011-010-110
-        get_triangle_from_vecs(v_011, v_001, v_010, color), // 011-001-010
-
-    ];
-
-    let mut old_idx = out_vec.len(); 
-    out_vec.append(&mut base_vecs);
-    idx_tup.push(("base".to_string(), old_idx, out_vec.len())); 
-
-
-    // wrist
-    size = 10.0;
-
-    cx = 0.0; 
-    cy = -20.0; 
-    cz = -15.0;
-
-    c1_x = cx - size;
-    c1_y = cy - size / 2.0;
-    c1_z = cz - size;
-
-    c2_x = cx + size;
-    c2_y = cy + size / 2.0;
-    c2_z = cz + size;
-
-    v_000 = V3 {x: c1_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_001 = V3 {x: c1_x as f64, y: c1_y as f64, z: c2_z as f64};
-    v_010 = V3 {x: c1_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_100 = V3 {x: c2_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_011 = V3 {x: c1_x as f64, y: c2_y as f64, z: c2_z as f64};
-    v_110 = V3 {x: c2_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_101 = V3 {x: c2_x as f64, y: c1_y as f64, z: c2_z as f64};
-
-    v_111 = V3 {x: c2_x as f64, y: c2_y as f64, z: c2_z as f64};
-
-    color = YELLOW;
-    let mut wrist_vecs = vec![
-    // triangles to make: 
-        get_triangle_from_vecs(v_000, v_001, v_010, color), // 000-001-010
-        get_triangle_from_vecs(v_000, v_001, v_100, color), // 000-001-100
-        get_triangle_from_vecs(v_000, v_010, v_100, color), // 000-010-100
-        
-        get_triangle_from_vecs(v_111, v_101, v_110, color), // 111-101-110
-        get_triangle_from_vecs(v_111, v_101, v_011, color), // 111-101-011
-        get_triangle_from_vecs(v_111, v_110, v_011, color), // 111-110-011
-
-        get_triangle_from_vecs(v_100, v_101, v_001, color), // 100-101-001
-        get_triangle_from_vecs(v_100, v_101, v_110, color), // 100-101-110
-        get_triangle_from_vecs(v_100, v_110, v_010, color), // 100-110-010
-
-        get_triangle_from_vecs(v_011, v_101, v_001, color), // 011-101-001
-        get_triangle_from_vecs(v_011, v_010, v_110, color), // 011-010-110
-        get_triangle_from_vecs(v_011, v_001, v_010, color), // 011-001-010
-
-    ]; 
-
-    let mut old_idx = out_vec.len(); 
-    out_vec.append(&mut wrist_vecs); 
-    idx_tup.push(("wrist".to_string(), old_idx, out_vec.len()));
-
-    // finger_1_top
-    size = 8.0;
-
-    cx = -20.0; 
-    cy = 75.0; 
-    cz = 10.0;
-
-    c1_x = cx - size;
-    c1_y = cy - size * 1.7;
-    c1_z = cz - size;
-
-    c2_x = cx + size;
-    c2_y = cy + size * 1.7;
-    c2_z = cz + size;
-
-    v_000 = V3 {x: c1_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_001 = V3 {x: c1_x as f64, y: c1_y as f64, z: c2_z as f64};
-    v_010 = V3 {x: c1_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_100 = V3 {x: c2_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_011 = V3 {x: c1_x as f64, y: c2_y as f64, z: c2_z as f64};
-    v_110 = V3 {x: c2_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_101 = V3 {x: c2_x as f64, y: c1_y as f64, z: c2_z as f64};
-
-    v_111 = V3 {x: c2_x as f64, y: c2_y as f64, z: c2_z as f64};
-
-    color = RED;
-    let mut finger_vecs = vec![
-    // triangles to make: 
-        get_triangle_from_vecs(v_000, v_001, v_010, color), // 000-001-010
-        get_triangle_from_vecs(v_000, v_001, v_100, color), // 000-001-100
-        get_triangle_from_vecs(v_000, v_010, v_100, color), // 000-010-100
-        
-        get_triangle_from_vecs(v_111, v_101, v_110, color), // 111-101-110
-        get_triangle_from_vecs(v_111, v_101, v_011, color), // 111-101-011
-        get_triangle_from_vecs(v_111, v_110, v_011, color), // 111-110-011
-
-        get_triangle_from_vecs(v_100, v_101, v_001, color), // 100-101-001
-        get_triangle_from_vecs(v_100, v_101, v_110, color), // 100-101-110
-        get_triangle_from_vecs(v_100, v_110, v_010, color), // 100-110-010
-
-        get_triangle_from_vecs(v_011, v_101, v_001, color), // 011-101-001
-        get_triangle_from_vecs(v_011, v_010, v_110, color), // 011-010-110
-        get_triangle_from_vecs(v_011, v_001, v_010, color), // 011-001-010
-
-    ]; 
-
-    let mut old_idx = out_vec.len();
-    out_vec.append(&mut finger_vecs); 
-    idx_tup.push(("finger_1_top".to_string(), old_idx, out_vec.len()));
-
-    // finger_1_bot
-    size = 8.0;
-
-    cx = -20.0; 
-    cy = 45.0; 
-    cz = 10.0;
-
-    c1_x = cx - size;
-    c1_y = cy - size * 1.7;
-    c1_z = cz - size;
-
-    c2_x = cx + size;
-    c2_y = cy + size * 1.7;
-    c2_z = cz + size;
-
-    v_000 = V3 {x: c1_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_001 = V3 {x: c1_x as f64, y: c1_y as f64, z: c2_z as f64};
-    v_010 = V3 {x: c1_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_100 = V3 {x: c2_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_011 = V3 {x: c1_x as f64, y: c2_y as f64, z: c2_z as f64};
-    v_110 = V3 {x: c2_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_101 = V3 {x: c2_x as f64, y: c1_y as f64, z: c2_z as f64};
-
-    v_111 = V3 {x: c2_x as f64, y: c2_y as f64, z: c2_z as f64};
-
-    color = YELLOW;
-    let mut finger_vecs = vec![
-    // triangles to make: 
-        get_triangle_from_vecs(v_000, v_001, v_010, color), // 000-001-010
-        get_triangle_from_vecs(v_000, v_001, v_100, color), // 000-001-100
-        get_triangle_from_vecs(v_000, v_010, v_100, color), // 000-010-100
-        
-        get_triangle_from_vecs(v_111, v_101, v_110, color), // 111-101-110
-        get_triangle_from_vecs(v_111, v_101, v_011, color), // 111-101-011
-        get_triangle_from_vecs(v_111, v_110, v_011, color), // 111-110-011
-
-        get_triangle_from_vecs(v_100, v_101, v_001, color), // 100-101-001
-        get_triangle_from_vecs(v_100, v_101, v_110, color), // 100-101-110
-        get_triangle_from_vecs(v_100, v_110, v_010, color), // 100-110-010
-
-        get_triangle_from_vecs(v_011, v_101, v_001, color), // 011-101-001
-        get_triangle_from_vecs(v_011, v_010, v_110, color), // 011-010-110
-        get_triangle_from_vecs(v_011, v_001, v_010, color), // 011-001-010
-
-    ]; 
-
-    let mut old_idx = out_vec.len();
-    out_vec.append(&mut finger_vecs);
-    idx_tup.push(("finger_1_bot".to_string(), old_idx, out_vec.len()));
-
-    // finger_2_top
-    size = 8.0;
-
-    cx = 0.0; 
-    cy = 75.0; 
-    cz = 10.0;
-
-    c1_x = cx - size;
-    c1_y = cy - size * 1.7;
-    c1_z = cz - size;
-
-    c2_x = cx + size;
-    c2_y = cy + size * 1.7;
-    c2_z = cz + size;
-
-    v_000 = V3 {x: c1_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_001 = V3 {x: c1_x as f64, y: c1_y as f64, z: c2_z as f64};
-    v_010 = V3 {x: c1_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_100 = V3 {x: c2_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_011 = V3 {x: c1_x as f64, y: c2_y as f64, z: c2_z as f64};
-    v_110 = V3 {x: c2_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_101 = V3 {x: c2_x as f64, y: c1_y as f64, z: c2_z as f64};
-
-    v_111 = V3 {x: c2_x as f64, y: c2_y as f64, z: c2_z as f64};
-
-    color = RED;
-    let mut finger_vecs = vec![
-    // triangles to make: 
-        get_triangle_from_vecs(v_000, v_001, v_010, color), // 000-001-010
-        get_triangle_from_vecs(v_000, v_001, v_100, color), // 000-001-100
-        get_triangle_from_vecs(v_000, v_010, v_100, color), // 000-010-100
-        
-        get_triangle_from_vecs(v_111, v_101, v_110, color), // 111-101-110
-        get_triangle_from_vecs(v_111, v_101, v_011, color), // 111-101-011
-        get_triangle_from_vecs(v_111, v_110, v_011, color), // 111-110-011
-
-        get_triangle_from_vecs(v_100, v_101, v_001, color), // 100-101-001
-        get_triangle_from_vecs(v_100, v_101, v_110, color), // 100-101-110
-        get_triangle_from_vecs(v_100, v_110, v_010, color), // 100-110-010
-
-        get_triangle_from_vecs(v_011, v_101, v_001, color), // 011-101-001
-        get_triangle_from_vecs(v_011, v_010, v_110, color), // 011-010-110
-        get_triangle_from_vecs(v_011, v_001, v_010, color), // 011-001-010
-
-    ]; 
-
-    let mut old_idx = out_vec.len();
-    out_vec.append(&mut finger_vecs); 
-    idx_tup.push(("finger_2_top".to_string(), old_idx, out_vec.len()));
-
-    // finger_2_bot
-    size = 8.0;
-
-    cx = 0.0; 
-    cy = 45.0; 
-    cz = 10.0;
-
-    c1_x = cx - size;
-    c1_y = cy - size * 1.7;
-    c1_z = cz - size;
-
-    c2_x = cx + size;
-    c2_y = cy + size * 1.7;
-    c2_z = cz + size;
-
-    v_000 = V3 {x: c1_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_001 = V3 {x: c1_x as f64, y: c1_y as f64, z: c2_z as f64};
-    v_010 = V3 {x: c1_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_100 = V3 {x: c2_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_011 = V3 {x: c1_x as f64, y: c2_y as f64, z: c2_z as f64};
-    v_110 = V3 {x: c2_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_101 = V3 {x: c2_x as f64, y: c1_y as f64, z: c2_z as f64};
-
-    v_111 = V3 {x: c2_x as f64, y: c2_y as f64, z: c2_z as f64};
-
-    color = YELLOW;
-    let mut finger_vecs = vec![
-    // triangles to make: 
-        get_triangle_from_vecs(v_000, v_001, v_010, color), // 000-001-010
-        get_triangle_from_vecs(v_000, v_001, v_100, color), // 000-001-100
-        get_triangle_from_vecs(v_000, v_010, v_100, color), // 000-010-100
-        
-        get_triangle_from_vecs(v_111, v_101, v_110, color), // 111-101-110
-        get_triangle_from_vecs(v_111, v_101, v_011, color), // 111-101-011
-        get_triangle_from_vecs(v_111, v_110, v_011, color), // 111-110-011
-
-        get_triangle_from_vecs(v_100, v_101, v_001, color), // 100-101-001
-        get_triangle_from_vecs(v_100, v_101, v_110, color), // 100-101-110
-        get_triangle_from_vecs(v_100, v_110, v_010, color), // 100-110-010
-
-        get_triangle_from_vecs(v_011, v_101, v_001, color), // 011-101-001
-        get_triangle_from_vecs(v_011, v_010, v_110, color), // 011-010-110
-        get_triangle_from_vecs(v_011, v_001, v_010, color), // 011-001-010
-
-    ]; 
-
-    let mut old_idx = out_vec.len();
-    out_vec.append(&mut finger_vecs); 
-    idx_tup.push(("finger_2_bot".to_string(), old_idx, out_vec.len()));
-
-    // finger_3_top
-    size = 8.0;
-
-    cx = 20.0; 
-    cy = 75.0; 
-    cz = 10.0;
-
-    c1_x = cx - size;
-    c1_y = cy - size * 1.7;
-    c1_z = cz - size;
-
-    c2_x = cx + size;
-    c2_y = cy + size * 1.7;
-    c2_z = cz + size;
-
-    v_000 = V3 {x: c1_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_001 = V3 {x: c1_x as f64, y: c1_y as f64, z: c2_z as f64};
-    v_010 = V3 {x: c1_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_100 = V3 {x: c2_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_011 = V3 {x: c1_x as f64, y: c2_y as f64, z: c2_z as f64};
-    v_110 = V3 {x: c2_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_101 = V3 {x: c2_x as f64, y: c1_y as f64, z: c2_z as f64};
-
-    v_111 = V3 {x: c2_x as f64, y: c2_y as f64, z: c2_z as f64};
-
-    color = RED;
-    let mut finger_vecs = vec![
-    // triangles to make: 
-        get_triangle_from_vecs(v_000, v_001, v_010, color), // 000-001-010
-        get_triangle_from_vecs(v_000, v_001, v_100, color), // 000-001-100
-        get_triangle_from_vecs(v_000, v_010, v_100, color), // 000-010-100
-        
-        get_triangle_from_vecs(v_111, v_101, v_110, color), // 111-101-110
-        get_triangle_from_vecs(v_111, v_101, v_011, color), // 111-101-011
-        get_triangle_from_vecs(v_111, v_110, v_011, color), // 111-110-011
-
-        get_triangle_from_vecs(v_100, v_101, v_001, color), // 100-101-001
-        get_triangle_from_vecs(v_100, v_101, v_110, color), // 100-101-110
-        get_triangle_from_vecs(v_100, v_110, v_010, color), // 100-110-010
-
-        get_triangle_from_vecs(v_011, v_101, v_001, color), // 011-101-001
-        get_triangle_from_vecs(v_011, v_010, v_110, color), // 011-010-110
-        get_triangle_from_vecs(v_011, v_001, v_010, color), // 011-001-010
-
-    ]; 
-
-    let mut old_idx = out_vec.len();
-    out_vec.append(&mut finger_vecs); 
-    idx_tup.push(("finger_3_top".to_string(), old_idx, out_vec.len()));
-
-    // finger_3_bot
-    size = 8.0;
-
-    cx = 20.0; 
-    cy = 45.0; 
-    cz = 10.0;
-
-    c1_x = cx - size;
-    c1_y = cy - size * 1.7;
-    c1_z = cz - size;
-
-    c2_x = cx + size;
-    c2_y = cy + size * 1.7;
-    c2_z = cz + size;
-
-    v_000 = V3 {x: c1_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_001 = V3 {x: c1_x as f64, y: c1_y as f64, z: c2_z as f64};
-    v_010 = V3 {x: c1_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_100 = V3 {x: c2_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_011 = V3 {x: c1_x as f64, y: c2_y as f64, z: c2_z as f64};
-    v_110 = V3 {x: c2_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_101 = V3 {x: c2_x as f64, y: c1_y as f64, z: c2_z as f64};
-
-    v_111 = V3 {x: c2_x as f64, y: c2_y as f64, z: c2_z as f64};
-
-    color = YELLOW;
-    let mut finger_vecs = vec![
-    // triangles to make: 
-        get_triangle_from_vecs(v_000, v_001, v_010, color), // 000-001-010
-        get_triangle_from_vecs(v_000, v_001, v_100, color), // 000-001-100
-        get_triangle_from_vecs(v_000, v_010, v_100, color), // 000-010-100
-        
-        get_triangle_from_vecs(v_111, v_101, v_110, color), // 111-101-110
-        get_triangle_from_vecs(v_111, v_101, v_011, color), // 111-101-011
-        get_triangle_from_vecs(v_111, v_110, v_011, color), // 111-110-011
+// a triangle on screen ready to hand out weights, each corner (x, y, 1/z)
+struct ScreenTriangle {p: [(f64, f64, f64); 3], area: f64}
+
+impl ScreenTriangle {
+    // the screen space weights at a point, negative off the triangle
+    fn linear(&self, px: f64, py: f64) -> [f64; 3] {
+        let [p0, p1, p2] = self.p;
+        [
+            edge_function(p1.0, p1.1, p2.0, p2.1, px, py) / self.area,
+            edge_function(p2.0, p2.1, p0.0, p0.1, px, py) / self.area,
+            edge_function(p0.0, p0.1, p1.0, p1.1, px, py) / self.area,
+        ]
+    }
 
-        get_triangle_from_vecs(v_100, v_101, v_001, color), // 100-101-001
-        get_triangle_from_vecs(v_100, v_101, v_110, color), // 100-101-110
-        get_triangle_from_vecs(v_100, v_110, v_010, color), // 100-110-010
+    // the same turned perspective correct
+    fn correct(&self, [w0, w1, w2]: [f64; 3]) -> [f64; 3] {
+        let [p0, p1, p2] = self.p;
+        let ooz = w0 * p0.2 + w1 * p1.2 + w2 * p2.2;
+        [w0 * p0.2 / ooz, w1 * p1.2 / ooz, w2 * p2.2 / ooz]
+    }
+}
 
-        get_triangle_from_vecs(v_011, v_101, v_001, color), // 011-101-001
-        get_triangle_from_vecs(v_011, v_010, v_110, color), // 011-010-110
-        get_triangle_from_vecs(v_011, v_001, v_010, color), // 011-001-010
+// what fill_triangle_3D_with hands `shade` for a pixel: the barycentric
+// weights of the three corners there, and on asking how much they change
+// going one pixel right (dx) and one pixel down (dy), which textures use to
+// tell how much of the image one pixel covers
+// those cost two more rounds of weights so only textures work them out
+#[derive(Clone, Copy)]
+struct Frag<'a> {w: [f64; 3], px: f64, py: f64, tri: &'a ScreenTriangle}
+
+impl Frag<'_> {
+    // the neighbours may be off the triangle, the weights just go negative
+    // there which is fine for a difference
+    fn change_to(&self, px: f64, py: f64) -> [f64; 3] {
+        let n = self.tri.correct(self.tri.linear(px, py));
+        [n[0] - self.w[0], n[1] - self.w[1], n[2] - self.w[2]]
+    }
 
-    ]; 
+    fn dx(&self) -> [f64; 3] {
+        self.change_to(self.px + 1.0, self.py)
+    }
 
-    let mut old_idx = out_vec.len();
-    out_vec.append(&mut finger_vecs); 
-    idx_tup.push(("finger_3_bot".to_string(), old_idx, out_vec.len()));
+    fn dy(&self) -> [f64; 3] {
+        self.change_to(self.px, self.py + 1.0)
+    }
+}
 
-    // finger_4_top
-    size = 8.0;
+// the same fill, but every pixel that passes the depth test asks `shade` for
+// its color, handing it the weights of the three corners in a Frag
+// the weights are perspective correct: blended as w/z and divided by the
+// blended 1/z, otherwise colors slide around on anything seen at an angle
+fn fill_triangle_3D_with(buf: &mut [u32], zbuf: &mut [f64], p: [(f64, f64, f64); 3], shade: impl Fn(Frag) -> u32) {
+    let [p0, p1, p2] = p;
+    let min_x = (p0.0.min(p1.0.min(p2.0)).floor() as i32).max(0);
+    let max_x = (p0.0.max(p1.0.max(p2.0)).ceil() as i32).min(WIDTH as i32 - 1);
+    let min_y = (p0.1.min(p1.1.min(p2.1)).floor() as i32).max(0);
+    let max_y = (p0.1.max(p1.1.max(p2.1)).ceil() as i32).min(HEIGHT as i32 - 1);
+
+    let area = edge_function(p0.0, p0.1, p1.0, p1.1, p2.0, p2.1);
+    if area == 0.0 {
+        return; // degenerate, nothing to fill
+    }
+    let tri = ScreenTriangle {p, area};
 
-    cx = 40.0; 
-    cy = 20.0; 
-    cz = 10.0;
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            // sample the middle of the pixel
+            let px = x as f64 + 0.5;
+            let py = y as f64 + 0.5;
 
-    c1_x = cx - size;
-    c1_y = cy - size;
-    c1_z = cz - size;
+            let [w0, w1, w2] = tri.linear(px, py);
 
-    c2_x = cx + size;
-    c2_y = cy + size;
-    c2_z = cz + size;
+            // dividing by the area already took care of the winding
+            if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                continue;
+            }
 
-    v_000 = V3 {x: c1_x as f64, y: c1_y as f64, z: c1_z as f64};
+            let ooz = w0 * p0.2 + w1 * p1.2 + w2 * p2.2;
+            let idx = y as usize * WIDTH + x as usize;
+            if ooz > zbuf[idx] {
+                zbuf[idx] = ooz;
+                let w = [w0 * p0.2 / ooz, w1 * p1.2 / ooz, w2 * p2.2 / ooz];
+                buf[idx] = shade(Frag {w, px, py, tri: &tri});
+            }
+        }
+    }
+}
 
-    v_001 = V3 {x: c1_x as f64, y: c1_y as f64, z: c2_z as f64};
-    v_010 = V3 {x: c1_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_100 = V3 {x: c2_x as f64, y: c1_y as f64, z: c1_z as f64};
+// Unlike the 2D shapes that I just draw straight to the buffer, my idea
+// with the 3D shapes is to create generators that output a list of triangles
+// that can be then projected one at a time onto the buffer
+// this will make doing manipulations like rotations, transforms, and translations easier
+// ...once I get there
+// size is half the edge, the cube goes from c - size to c + size on every axis
+#[cfg(test)]
+fn get_cube_triangles(size: f64, cx: f64, cy: f64, cz: f64, color: u32) -> Vec<Triangle3d> {
+    get_box_triangles(V3::new(cx, cy, cz), V3::new(size, size, size), [color; 6])
+}
 
-    v_011 = V3 {x: c1_x as f64, y: c2_y as f64, z: c2_z as f64};
-    v_110 = V3 {x: c2_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_101 = V3 {x: c2_x as f64, y: c1_y as f64, z: c2_z as f64};
+// which slot of the face color array goes on which side of a box
+const BOX_FACES: [V3; 6] = [
+    V3::new(-1.0, 0.0, 0.0), V3::new(1.0, 0.0, 0.0),
+    V3::new(0.0, -1.0, 0.0), V3::new(0.0, 1.0, 0.0),
+    V3::new(0.0, 0.0, -1.0), V3::new(0.0, 0.0, 1.0),
+];
+
+// the spinning cube's colors: green on the sides, blue top and bottom, red front and back
+#[cfg(test)]
+const SPINNING_CUBE_COLORS: [u32; 6] = [GREEN, GREEN, BLUE, BLUE, RED, RED];
+
+// a box from its center and half sizes, two triangles per face in the
+// -x, +x, -y, +y, -z, +z order of BOX_FACES, each face its own color
+// both triangles of a face share the a-c diagonal and go round clockwise
+// seen from outside, so the normals point out
+fn get_box_triangles(center: V3, half: V3, colors: [u32; 6]) -> Vec<Triangle3d> {
+    let mut out = Vec::with_capacity(12);
+    for (normal, color) in BOX_FACES.into_iter().zip(colors) {
+        // two directions along the face with u x v = normal
+        let (u, v) = match (normal.x != 0.0, normal.y != 0.0) {
+            (true, _) => (V3::new(0.0, 1.0, 0.0), V3::new(0.0, 0.0, 1.0)),
+            (_, true) => (V3::new(0.0, 0.0, 1.0), V3::new(1.0, 0.0, 0.0)),
+            _ => (V3::new(1.0, 0.0, 0.0), V3::new(0.0, 1.0, 0.0)),
+        };
+        let (u, v) = if normal.x + normal.y + normal.z < 0.0 {(v, u)} else {(u, v)};
+
+        let corner = |su: f64, sv: f64| {
+            let p = normal + u * su + v * sv;
+            center + V3::new(p.x * half.x, p.y * half.y, p.z * half.z)
+        };
+        let (a, b, c, d) = (corner(-1.0, -1.0), corner(1.0, -1.0), corner(1.0, 1.0), corner(-1.0, 1.0));
+        // every face gets the whole texture, u along the first direction and v the second
+        let (ua, ub, uc, ud) = (V2::new(0.0, 0.0), V2::new(1.0, 0.0), V2::new(1.0, 1.0), V2::new(0.0, 1.0));
+        let mut first = get_triangle_from_vecs(a, b, c, color);
+        (first.uv0, first.uv1, first.uv2) = (ua, ub, uc);
+        let mut second = get_triangle_from_vecs(a, c, d, color);
+        (second.uv0, second.uv1, second.uv2) = (ua, uc, ud);
+        out.extend([first, second]);
+    }
+    out
+}
 
-    v_111 = V3 {x: c2_x as f64, y: c2_y as f64, z: c2_z as f64};
+// draws the bones of a posed skeleton on top of whatever is in the buffer,
+// a line from every joint to its parent and a dot on each joint
+fn draw_skeleton(buffer: &mut [u32], camera: &Camera, skeleton: &Skeleton, style: LineStyle) {
+    let positions = skeleton.world_positions();
+    let points: Vec<_> = positions.iter().map(|p| project_3D(camera, *p)).collect();
 
-    color = RED;
-    let mut finger_vecs = vec![
-    // triangles to make: 
-        get_triangle_from_vecs(v_000, v_001, v_010, color), // 000-001-010
-        get_triangle_from_vecs(v_000, v_001, v_100, color), // 000-001-100
-        get_triangle_from_vecs(v_000, v_010, v_100, color), // 000-010-100
-        
-        get_triangle_from_vecs(v_111, v_101, v_110, color), // 111-101-110
-        get_triangle_from_vecs(v_111, v_101, v_011, color), // 111-101-011
-        get_triangle_from_vecs(v_111, v_110, v_011, color), // 111-110-011
+    for (i, joint) in skeleton.joints().iter().enumerate() {
+        let Some((x, y, _)) = points[i] else { continue };
+        if let Some(Some((px, py, _))) = joint.parent.map(|p| points[p]) {
+            make_line_styled(buffer, x, y, px, py, GREEN, style);
+        }
+        make_square_filled(buffer, x as i32 as usize, y as i32 as usize, 6, WHITE);
+    }
+}
 
-        get_triangle_from_vecs(v_100, v_101, v_001, color), // 100-101-001
-        get_triangle_from_vecs(v_100, v_101, v_110, color), // 100-101-110
-        get_triangle_from_vecs(v_100, v_110, v_010, color), // 100-110-010
+// takes a list of triangles and adds them to the buffer, 
+// Three.js does something like this with world.add()
+fn draw_3d_from_triangles(buf: &mut [u32], zbuf: &mut [f64], camera: &Camera, lighting: &Lighting, culling: &Culling, triangles: Vec<Triangle3d>) {
+    let to_clip = camera.view_projection();
+    for triangle in triangles {
+        make_triangle_3D(buf, zbuf, camera, &to_clip, lighting, culling, triangle); 
+    }
+}
 
-        get_triangle_from_vecs(v_011, v_101, v_001, color), // 011-101-001
-        get_triangle_from_vecs(v_011, v_010, v_110, color), // 011-010-110
-        get_triangle_from_vecs(v_011, v_001, v_010, color), // 011-001-010
+// same thing for the wireframe look, no depth needed since lines don't hide anything
+fn draw_3d_wireframe_from_triangles(buf: &mut [u32], camera: &Camera, triangles: Vec<Triangle3d>, style: LineStyle) {
+    let to_clip = camera.view_projection();
+    for triangle in triangles {
+        make_triangle_3D_wireframe(buf, &to_clip, triangle, style); 
+    }
+}
 
-    ]; 
+// run every vertex through a matrix, the normals only get the rotation
+// the normals go through the inverse transpose so they stay right on
+// squashed shapes, and a mirroring matrix swaps two corners so the triangle
+// still goes round clockwise from outside
+fn transform_triangles(mut triangles: Vec<Triangle3d>, m: &Mat4) -> Vec<Triangle3d> {
+    for triangle in &mut triangles {
+        transform_triangle(triangle, m);
+    }
+    triangles
+}
 
-    let mut old_idx = out_vec.len();
-    out_vec.append(&mut finger_vecs); 
-    idx_tup.push(("finger_4_top".to_string(), old_idx, out_vec.len()));
+fn transform_triangle(triangle: &mut Triangle3d, m: &Mat4) {
+    triangle.v0 = m.transform_point(triangle.v0);
+    triangle.v1 = m.transform_point(triangle.v1);
+    triangle.v2 = m.transform_point(triangle.v2);
+    let normals = m.normal_matrix();
+    triangle.n0 = normals.transform_dir(triangle.n0).normalize();
+    triangle.n1 = normals.transform_dir(triangle.n1).normalize();
+    triangle.n2 = normals.transform_dir(triangle.n2).normalize();
+    if m.det3() < 0.0 {
+        (triangle.v1, triangle.v2) = (triangle.v2, triangle.v1);
+        (triangle.n1, triangle.n2) = (triangle.n2, triangle.n1);
+        (triangle.uv1, triangle.uv2) = (triangle.uv2, triangle.uv1);
+    }
+}
 
-    // finger_4_bot
-    size = 8.0;
+// rotate a list triangles, think of each call of this as using one rotation
+// matrix and order matters as matrix mult is not commutative
+#[cfg(test)]
+fn rotate_triangles(triangles: Vec<Triangle3d>, ax: f64, ay: f64, az: f64) -> Vec<Triangle3d> {
+    transform_triangles(triangles, &Mat4::rotate(V3::new(ax, ay, az)))
+}
 
-    cx = 40.0; 
-    cy = 0.0; 
-    cz = 10.0;
+// same idea with a quaternion, no gimbal lock to worry about
+// a rotation never mirrors, so the normals can go through it as they are
+fn rotate_triangle_quat(triangle: Triangle3d, q: Quat) -> Triangle3d {
+    Triangle3d {
+        v0: q.rotate(triangle.v0),
+        v1: q.rotate(triangle.v1),
+        v2: q.rotate(triangle.v2),
+        n0: q.rotate(triangle.n0),
+        n1: q.rotate(triangle.n1),
+        n2: q.rotate(triangle.n2),
+        ..triangle
+    }
+}
 
-    c1_x = cx - size * 1.2;
-    c1_y = cy - size;
-    c1_z = cz - size;
+fn rotate_triangles_quat(triangles: Vec<Triangle3d>, q: Quat) -> Vec<Triangle3d> {
+    triangles.into_iter().map(|t| rotate_triangle_quat(t, q)).collect()
+}
 
-    c2_x = cx + size * 1.2;
-    c2_y = cy + size;
-    c2_z = cz + size;
 
-    v_000 = V3 {x: c1_x as f64, y: c1_y as f64, z: c1_z as f64};
+// hand poses by joint angle, negative x curls a finger towards the camera
+// (the palm side) and positive z folds the thumb in across the palm
+const HAND_OPEN: &Pose = &[];
+
+const HAND_FIST: &Pose = &[
+    ("knuckle_1", V3 {x: -1.5, y: 0.0, z: 0.0}),
+    ("knuckle_2", V3 {x: -1.5, y: 0.0, z: 0.0}),
+    ("knuckle_3", V3 {x: -1.5, y: 0.0, z: 0.0}),
+    ("finger_1_mid", V3 {x: -1.6, y: 0.0, z: 0.0}),
+    ("finger_2_mid", V3 {x: -1.6, y: 0.0, z: 0.0}),
+    ("finger_3_mid", V3 {x: -1.6, y: 0.0, z: 0.0}),
+    ("knuckle_4", V3 {x: -0.3, y: 0.0, z: 0.9}),
+    ("finger_4_mid", V3 {x: -0.8, y: 0.0, z: 0.0}),
+];
+
+// finger_3 is the one next to the thumb so it gets to do the pointing
+const HAND_POINT: &Pose = &[
+    ("knuckle_1", V3 {x: -1.5, y: 0.0, z: 0.0}),
+    ("knuckle_2", V3 {x: -1.5, y: 0.0, z: 0.0}),
+    ("finger_1_mid", V3 {x: -1.6, y: 0.0, z: 0.0}),
+    ("finger_2_mid", V3 {x: -1.6, y: 0.0, z: 0.0}),
+    ("knuckle_4", V3 {x: -0.3, y: 0.0, z: 0.9}),
+    ("finger_4_mid", V3 {x: -0.8, y: 0.0, z: 0.0}),
+];
+
+fn hand_pose(name: &str) -> Option<&'static Pose> {
+    match name {
+        "open" => Some(HAND_OPEN),
+        "fist" => Some(HAND_FIST),
+        "point" => Some(HAND_POINT),
+        _ => None,
+    }
+}
 
-    v_001 = V3 {x: c1_x as f64, y: c1_y as f64, z: c2_z as f64};
-    v_010 = V3 {x: c1_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_100 = V3 {x: c2_x as f64, y: c1_y as f64, z: c1_z as f64};
+// the default show: open hand, make a fist, point, and back to open,
+// one second between each pose
+fn hand_clip(interp: Interp, mode: PlayMode) -> Clip {
+    Clip::from_poses(&[(0.0, HAND_OPEN), (1.0, HAND_FIST), (2.0, HAND_POINT), (3.0, HAND_OPEN)], interp, mode)
+}
 
-    v_011 = V3 {x: c1_x as f64, y: c2_y as f64, z: c2_z as f64};
-    v_110 = V3 {x: c2_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_101 = V3 {x: c2_x as f64, y: c1_y as f64, z: c2_z as f64};
+// poses the hand for `time` seconds into the clip, the joint limits clamp
+// whatever the clip asks for that a real hand can't do
+// joints the hand file doesn't have are left alone
+fn animate_hand(skeleton: &mut Skeleton, clip: &Clip, time: f64) {
+    for (joint, angles) in clip.sample(time) {
+        if skeleton.index(joint).is_some() {
+            skeleton.set_angles(joint, angles);
+        }
+    }
+}
 
-    v_111 = V3 {x: c2_x as f64, y: c2_y as f64, z: c2_z as f64};
+// --fixed-step: the clip only moves on in whole steps and every frame is
+// drawn part way between the last two, like the spinning cube's angle
+struct SteppedClip {
+    sim: FixedStep,
+    prev: f64, // clip time at the step before last
+    time: f64, // clip time at the last step
+}
 
-    color = YELLOW;
-    let mut finger_vecs = vec![
-    // triangles to make: 
-        get_triangle_from_vecs(v_000, v_001, v_010, color), // 000-001-010
-        get_triangle_from_vecs(v_000, v_001, v_100, color), // 000-001-100
-        get_triangle_from_vecs(v_000, v_010, v_100, color), // 000-010-100
-        
-        get_triangle_from_vecs(v_111, v_101, v_110, color), // 111-101-110
-        get_triangle_from_vecs(v_111, v_101, v_011, color), // 111-101-011
-        get_triangle_from_vecs(v_111, v_110, v_011, color), // 111-110-011
+impl SteppedClip {
+    fn new(step: f64) -> SteppedClip {
+        SteppedClip {sim: FixedStep::new(step), prev: 0.0, time: 0.0}
+    }
 
-        get_triangle_from_vecs(v_100, v_101, v_001, color), // 100-101-001
-        get_triangle_from_vecs(v_100, v_101, v_110, color), // 100-101-110
-        get_triangle_from_vecs(v_100, v_110, v_010, color), // 100-110-010
+    // after every frame with how long it took
+    fn advance(&mut self, dt: f64) {
+        for _ in 0..self.sim.advance(dt) {
+            self.prev = self.time;
+            self.time += self.sim.step;
+        }
+    }
 
-        get_triangle_from_vecs(v_011, v_101, v_001, color), // 011-101-001
-        get_triangle_from_vecs(v_011, v_010, v_110, color), // 011-010-110
-        get_triangle_from_vecs(v_011, v_001, v_010, color), // 011-001-010
+    // the joint angles of the last two steps blended by how far it is to the next one
+    fn pose(&self, skeleton: &mut Skeleton, clip: &Clip) {
+        let alpha = self.sim.alpha();
+        for ((joint, before), (_, after)) in clip.sample(self.prev).into_iter().zip(clip.sample(self.time)) {
+            if skeleton.index(joint).is_some() {
+                skeleton.set_angles(joint, before.lerp(after, alpha));
+            }
+        }
+    }
+}
 
-    ];
+// one frame of the hand into a fresh buffer, shared by the window and headless modes
+// filled, or as lines in that style with --wireframe
+fn render_hand(hand_triangles: &[Triangle3d], camera: &Camera, lighting: &Lighting, culling: &Culling, wireframe: Option<LineStyle>) -> Vec<u32> {
+    let mut buffer = reset_screen(); 
+    let mut zbuf = reset_zbuf();
+    // make_square(&mut buffer, 300, 300, 100, GREEN);
+    // make_square(&mut buffer, 300, 300, 80, GREEN);
 
-    let mut old_idx = out_vec.len();
-    out_vec.append(&mut finger_vecs); 
-    idx_tup.push(("finger_4_bot".to_string(), old_idx, out_vec.len()));
+    // make_square(&mut buffer, 300, 300, 150, YELLOW); 
     
+    // let cube1 = get_cube_triangles(35.0, 50.0, 50.0, 50.0, GREEN); 
+    // draw_3d_from_triangles(&mut buffer, &mut zbuf, camera, cube1);
 
+    // let cube2 = get_cube_triangles(35.0, 50.0, 35.0, 50.0, RED); 
+    // draw_3d_from_triangles(&mut buffer, &mut zbuf, camera, cube2); 
+    // let cube3 = rotate_triangles(get_box_triangles(V3::ZERO, V3::new(50.0, 50.0, 50.0), SPINNING_CUBE_COLORS), angle, -1.0 * angle, -0.5 * angle);
 
-    // palm
-    size = 30.0;
-
-    cx = 0.0; 
-    cy = 9.0; 
-    cz = 50.0;
-
-    c1_x = cx - size;
-    c1_y = cy - size;
-    c1_z = cz - size / 1.5;
-
-    c2_x = cx + size;
-    c2_y = cy + size;
-    c2_z = cz + size / 1.5;
-
-    v_000 = V3 {x: c1_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_001 = V3 {x: c1_x as f64, y: c1_y as f64, z: c2_z as f64};
-    v_010 = V3 {x: c1_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_100 = V3 {x: c2_x as f64, y: c1_y as f64, z: c1_z as f64};
-
-    v_011 = V3 {x: c1_x as f64, y: c2_y as f64, z: c2_z as f64};
-    v_110 = V3 {x: c2_x as f64, y: c2_y as f64, z: c1_z as f64};
-    v_101 = V3 {x: c2_x as f64, y: c1_y as f64, z: c2_z as f64};
-
-    v_111 = V3 {x: c2_x as f64, y: c2_y as f64, z: c2_z as f64};
-
-    color = RED;
-    let mut palm_vecs = vec![
-    // triangles to make: 
-        get_triangle_from_vecs(v_000, v_001, v_010, color), // 000-001-010
-        get_triangle_from_vecs(v_000, v_001, v_100, color), // 000-001-100
-        get_triangle_from_vecs(v_000, v_010, v_100, color), // 000-010-100
-        
-        get_triangle_from_vecs(v_111, v_101, v_110, color), // 111-101-110
-        get_triangle_from_vecs(v_111, v_101, v_011, color), // 111-101-011
-        get_triangle_from_vecs(v_111, v_110, v_011, color), // 111-110-011
-
-        get_triangle_from_vecs(v_100, v_101, v_001, color), // 100-101-001
-        get_triangle_from_vecs(v_100, v_101, v_110, color), // 100-101-110
-        get_triangle_from_vecs(v_100, v_110, v_010, color), // 100-110-010
-
-        get_triangle_from_vecs(v_011, v_101, v_001, color), // 011-101-001
-        get_triangle_from_vecs(v_011, v_010, v_110, color), // 011-010-110
-        get_triangle_from_vecs(v_011, v_001, v_010, color), // 011-001-010
-
-    ]; 
-
-    let mut old_idx = out_vec.len();
-    out_vec.append(&mut palm_vecs);
-    idx_tup.push(("palm".to_string(), old_idx, out_vec.len()));
-
-    println!("{:?}", idx_tup); 
-    return (out_vec, idx_tup); 
+    // hand_triangles = rotate_triangles(hand_triangles.clone(),  angle, -1.0 * angle, -0.5 * angle);
+    match wireframe {
+        Some(style) => draw_3d_wireframe_from_triangles(&mut buffer, camera, hand_triangles.to_vec(), style),
+        None => draw_3d_from_triangles(&mut buffer, &mut zbuf, camera, lighting, culling, hand_triangles.to_vec()),
+    }
+    buffer
 }
 
-fn make_hand_skel(buffer: &mut [u32]) {
-    make_line(buffer, 400, 500, 400, 360, GREEN); 
-    make_line(buffer, 400, 360, 345, 300, GREEN); 
-    make_line(buffer, 400, 360, 450, 300, GREEN); 
-    make_line(buffer, 400, 360, 400, 300, GREEN); 
+// scale and move a loaded model so it sits around the origin about `size` units
+// across, obj files come in whatever units the artist felt like
+fn fit_triangles(triangles: Vec<Triangle3d>, size: f64) -> Vec<Triangle3d> {
+    let mut min = V3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+    let mut max = V3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for tri in &triangles {
+        for v in [tri.v0, tri.v1, tri.v2] {
+            min = min.min(v);
+            max = max.max(v);
+        }
+    }
 
-    make_line(buffer, 400, 400, 500, 400, GREEN); 
-    make_line(buffer, 500, 400, 500, 350, GREEN); 
+    let size_now = max - min;
+    let extent = size_now.x.max(size_now.y).max(size_now.z);
+    if triangles.is_empty() || extent <= 0.0 {
+        return triangles;
+    }
+    let scale = size / extent;
+    let center = (min + max) / 2.0;
+    let fit = Mat4::scale(V3::new(scale, scale, scale)) * Mat4::translate(-center);
+    transform_triangles(triangles, &fit)
+}
 
-    make_line(buffer, 450, 200, 450, 300, GREEN); 
-    make_line(buffer, 345, 200, 345, 300, GREEN); 
-    make_line(buffer, 400, 200, 400, 300, GREEN); 
+// --model files by extension, stl has no parts so the whole thing is one
+// named after the file
+// obj and stl have no normals worth using so they get smoothed ones, ply and
+// gltf bring their own (or smooth themselves when they don't)
+fn load_mesh(path: &std::path::Path) -> Result<(Vec<Triangle3d>, obj::Parts), String> {
+    let (mut triangles, parts) = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("obj") => obj::load_obj(path, WHITE)?,
+        Some("stl") => {
+            let triangles = stl::load_stl(path, WHITE)?;
+            let name = path.file_stem().map_or("model".to_string(), |s| s.to_string_lossy().into_owned());
+            let len = triangles.len();
+            (triangles, vec![(name, 0, len)])
+        }
+        Some("ply") => return ply::load_ply(path, WHITE),
+        Some("gltf") => return gltf::load_gltf(path, WHITE),
+        _ => return Err(format!("{}: don't know how to load that, expected .obj, .stl, .ply or .gltf", path.display())),
+    };
+    smooth_normals(&mut triangles);
+    Ok((triangles, parts))
 }
 
-// takes a list of triangles and adds them to the buffer, 
-// Three.js does something like this with world.add()
-fn draw_3d_from_triangles(buf: &mut [u32], triangles: Vec<Triangle3d>) {
-    for triangle in triangles {
-        make_triangle_3D(buf, triangle); 
+// --texture is an image file, or "checker" for a made up one that shows
+// where the uvs land
+fn load_texture(name: &str) -> Result<Texture, String> {
+    if name == "checker" {
+        return Ok(Texture::checker(64, 8, WHITE, 0x404040));
     }
+    Texture::load(std::path::Path::new(name))
 }
 
-// this probably works but my cube rendering implementation is wrong right now
-// so I can't verify it lol; I'll get back to this at some point
-// unused and I'm probably going to remove it
-fn rotate_y_triangle(triangles: Vec<Triangle3d>, angle: f64) -> Vec<Triangle3d> {
-    let sin_a = angle.sin();
-    let cos_a = angle.cos();
-
-    let mut out_vec: Vec<Triangle3d> = vec![];
-    
-    for triangle in triangles {
-        let vecs = vec![triangle.v0, triangle.v1, triangle.v2]; 
-        let mut new_vecs: Vec<V3> = vec![];  
-        for v in vecs {
-            new_vecs.push(rotate_y(v, angle));  
+// what --texture, --filter, --wrap and --aniso say goes on every triangle,
+// over whatever the file gave them
+fn retexture(mesh: &mut Mesh, texture: Option<&Arc<Texture>>, filter: Option<Filter>, wrap: Option<Wrap>, anisotropy: Option<usize>) {
+    for tri in &mut mesh.triangles {
+        if let Some(texture) = texture {
+            tri.material.texture = Some(texture.clone());
         }
-        out_vec.push(get_triangle_from_vecs(new_vecs[0], new_vecs[1], new_vecs[2], triangle.color)); 
-    } 
-    return out_vec; 
-    
+        if let Some(filter) = filter {
+            tri.material.sampler.filter = filter;
+        }
+        if let Some(wrap) = wrap {
+            tri.material.sampler.wrap = wrap;
+        }
+        if let Some(anisotropy) = anisotropy {
+            tri.material.sampler.anisotropy = anisotropy;
+        }
+    }
 }
 
-// rotate a list triangles, think of each call of this as using one rotation
-// matrix and order matters as matrix mult is not commutative
-fn rotate_triangles(mut triangles: Vec<Triangle3d>, ax: f64, ay: f64, az: f64) -> Vec<Triangle3d> {
-    let mut out_vec: Vec<Triangle3d> = vec![];
-    
-    for mut triangle in &mut triangles {
-        let v0 = rotate_point(triangle.v0.x, triangle.v0.y, triangle.v0.z, ax, ay, az);
-        let v1 = rotate_point(triangle.v1.x, triangle.v1.y, triangle.v1.z, ax, ay, az);
-        let v2 = rotate_point(triangle.v2.x, triangle.v2.y, triangle.v2.z, ax, ay, az); 
-
-        triangle.v0.x = v0.0; triangle.v0.y = v0.1; triangle.v0.z = v0.2;
-        triangle.v1.x = v1.0; triangle.v1.y = v1.1; triangle.v1.z = v1.2;
-        triangle.v2.x = v2.0; triangle.v2.y = v2.1; triangle.v2.z = v2.2; 
-
-    } 
-    return triangles; 
-    
+const MODEL_USAGE: &str = "    --hand <file.toml>  build the hand from this file (default models/hand.toml),
+                        edits to it show up in the window as soon as it's saved
+    --model <file>      draw a model from an obj, stl, ply or gltf file instead of the hand
+    --orient <x,y,z>    turn the --model this many degrees about x, then y, then z first,
+                        for files that think z is up
+    --export <file>     write the hand as it stands (after --pose) to an stl or obj file and quit,
+                        obj files get their colors in an mtl file next to them
+    --ascii             write an --export stl as ascii instead of binary
+    --no-mtl            write an --export obj on its own, without the mtl file or its colors
+    --texture <file>    put a png or ppm on every part, tinted by its color, or checker for a test pattern
+    --filter <mode>     how textures are read: nearest, bilinear or trilinear (default trilinear,
+                        the only one that uses mipmaps)
+    --aniso <n>         up to n trilinear lookups per pixel on faces seen edge on, 1 to 16 (default 1, off)
+    --wrap <mode>       what textures do past their edges: repeat or clamp (default repeat)
+    --pose <name>       hold the hand still in a pose: open, fist or point
+    --bones             draw the skeleton over the hand
+    --wireframe         draw the hand as the edges of its triangles
+    --smooth-lines      anti-alias the lines of --wireframe and --bones
+    --line-width <px>   how thick those lines are (default 1)
+    --interp <name>     how the hand animation blends poses: linear, cubic, ease or slerp (default ease)
+    --loop <mode>       how the hand animation repeats: once, loop or pingpong (default loop)
+    --view <yaw,pitch>  start the orbit camera turned this many degrees around the hand
+    --shading <mode>    unlit, flat, gouraud or phong (default phong)
+    --cull <faces>      which faces to skip: back, front or none (default back)
+    --winding <order>   which way front faces go around on screen: cw or ccw (default cw)
+    --show-back-faces   paint back faces magenta instead of culling them, to find bad winding";
+
+// where the hand lives unless --hand says otherwise
+const HAND_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/models/hand.toml");
+
+// how often the window checks the hand file for changes, in seconds
+const RELOAD_EVERY: f64 = 0.5;
+
+// headless frames are spaced like a 30 fps video no matter how long they take to render
+const HEADLESS_FPS: f64 = 30.0;
+
+// bad command line, say what and how to do it right
+fn usage_exit(msg: &str) -> ! {
+    eprintln!("{}\n{}\n{}\n{}", msg, output::USAGE, timing::USAGE, MODEL_USAGE);
+    std::process::exit(2);
 }
 
-// helper to rotate a specifc point
-fn rotate_point(i: f64, j: f64, k: f64, ax: f64, ay: f64, az: f64) -> (f64, f64, f64) {
-    let (mut x, mut y, mut z) = (i, j * ax.cos() - k * ax.sin(), j * ax.sin() + k * ax.cos());
-    let (nx, ny, nz) = (x * ay.cos() + z * ay.sin(), y, -x * ay.sin() + z * ay.cos());
-    let (fx, fy, fz) = (nx * az.cos() - ny * az.sin(), nx * az.sin() + ny * az.cos(), nz);
-
-    (fx, fy, fz)
+// pulls a plain on/off flag out of the args
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let Some(i) = args.iter().position(|a| a == flag) else { return false };
+    args.remove(i);
+    true
 }
 
-fn rotate_z(v: V3, angle: f64) -> V3 {
-    let cos = angle.cos();
-    let sin = angle.sin();
-    V3 {
-        x: v.x * cos - v.y * sin,
-        y: v.x * sin + v.y * cos,
-        z: v.z,
+// pulls `flag <value>` out of the args so the rest can go to the headless parser
+fn take_arg(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == flag)?;
+    if i + 1 >= args.len() {
+        usage_exit(&format!("{} needs a value", flag));
     }
+    let value = args.remove(i + 1);
+    args.remove(i);
+    Some(value)
 }
 
-
 fn main() {
-    let mut buffer: Vec<u32> = reset_screen(); 
-
-    let mut window = Window::new("Baby Steps", WIDTH, HEIGHT, WindowOptions::default())
-        .unwrap_or_else(|e| panic!("Error making a window! You goofed!: {}", e));
-    
-    let mut angle = 0.0;
-    let mut hand = wireframe_hand(BLUE); 
-
-    let mut hand_triangles = hand.0.clone(); 
-    let mut dir = 1.0; 
-
-    while window.is_open() && !window.is_key_down(Key::Q) {
-        buffer = reset_screen(); 
-        // make_square(&mut buffer, 300, 300, 100, GREEN);
-        // make_square(&mut buffer, 300, 300, 80, GREEN);
-
-        // make_square(&mut buffer, 300, 300, 150, YELLOW); 
-        // make_triangle_2D(&mut buffer, 100, 500, 350, 200, 500, 500, GREEN);
-        // make_triangle_2D(&mut buffer, 150 - 100, 550 - 100, 400 - 100, 250 - 100, 550 - 100, 550 -100, RED); 
-        
-        // let cube1 = get_cube_triangles(35, 50, 50, 50, GREEN); 
-        // draw_3d_from_triangles(&mut buffer, cube1);
-
-        // let cube2 = get_cube_triangles(35, 50, 35, 50, RED); 
-        // draw_3d_from_triangles(&mut buffer, cube2); 
-        // let cube3 = rotate_triangles(get_cube_triangles(50, 50, 50, 50, BLUE), angle, -1.0 * angle, -0.5 * angle);
-        // make_hand_skel(&mut buffer); 
-        
-        // tops
-        let finger_1_top: Vec<_> = hand_triangles[24..36].to_vec();
-        let finger_1_top = rotate_triangles(finger_1_top, 0.0, 0.0, 1.5 * angle);
-        for (i, tri) in finger_1_top.into_iter().enumerate() {
-            hand_triangles[24 + i] = tri;
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let hand_file = take_arg(&mut args, "--hand").unwrap_or_else(|| HAND_FILE.to_string());
+    let model = take_arg(&mut args, "--model");
+    let orient = take_arg(&mut args, "--orient").map(|v| {
+        let angles: Vec<f64> = v.split(',').filter_map(|a| a.trim().parse().ok()).collect();
+        if angles.len() != 3 {
+            usage_exit(&format!("bad orientation: {}", v));
         }
-
-        let finger_2_top: Vec<_> = hand_triangles[48..60].to_vec();
-        let finger_2_top = rotate_triangles(finger_2_top, 0.0, 0.0, 1.5 * angle);
-        for (i, tri) in finger_2_top.into_iter().enumerate() {
-            hand_triangles[48 + i] = tri;
+        Quat::from_euler(V3::new(angles[0].to_radians(), angles[1].to_radians(), angles[2].to_radians()))
+    });
+    let export = take_arg(&mut args, "--export");
+    let ascii = take_flag(&mut args, "--ascii");
+    let no_mtl = take_flag(&mut args, "--no-mtl");
+    let texture = take_arg(&mut args, "--texture").map(|name| Arc::new(load_texture(&name).unwrap_or_else(|e| {
+        eprintln!("Couldn't load the texture: {}", e);
+        std::process::exit(1);
+    })));
+    let filter = take_arg(&mut args, "--filter").map(|name| Filter::parse(&name).unwrap_or_else(|| {
+        usage_exit(&format!("unknown filter: {}", name))
+    }));
+    let wrap = take_arg(&mut args, "--wrap").map(|name| Wrap::parse(&name).unwrap_or_else(|| {
+        usage_exit(&format!("unknown wrap mode: {}", name))
+    }));
+    let anisotropy = take_arg(&mut args, "--aniso").map(|n| match n.parse() {
+        Ok(n @ 1..=MAX_ANISOTROPY) => n,
+        _ => usage_exit(&format!("bad anisotropy: {}, expected 1 to {}", n, MAX_ANISOTROPY)),
+    });
+    let pose = take_arg(&mut args, "--pose").map(|name| hand_pose(&name).unwrap_or_else(|| {
+        usage_exit(&format!("unknown pose: {}", name))
+    }));
+    let bones = take_flag(&mut args, "--bones");
+    let wireframe = take_flag(&mut args, "--wireframe");
+    let mut lines = if take_flag(&mut args, "--smooth-lines") {LineStyle::SMOOTH} else {LineStyle::PLAIN};
+    if let Some(width) = take_arg(&mut args, "--line-width") {
+        lines.width = width.parse().ok().filter(|w: &f64| *w > 0.0 && *w <= 64.0)
+            .unwrap_or_else(|| usage_exit(&format!("bad line width: {}", width)));
+    }
+    let interp = take_arg(&mut args, "--interp").map_or(Interp::Ease, |name| Interp::parse(&name).unwrap_or_else(|| {
+        usage_exit(&format!("unknown interpolation: {}", name))
+    }));
+    let mode = take_arg(&mut args, "--loop").map_or(PlayMode::Loop, |name| PlayMode::parse(&name).unwrap_or_else(|| {
+        usage_exit(&format!("unknown loop mode: {}", name))
+    }));
+    let view = take_arg(&mut args, "--view").map(|v| {
+        let angles: Vec<f64> = v.split(',').filter_map(|a| a.trim().parse().ok()).collect();
+        if angles.len() != 2 {
+            usage_exit(&format!("bad view: {}", v));
         }
+        (angles[0].to_radians(), angles[1].to_radians())
+    });
+    let shading = take_arg(&mut args, "--shading").map_or(ShadeMode::Phong, |name| ShadeMode::parse(&name).unwrap_or_else(|| {
+        usage_exit(&format!("unknown shading: {}", name))
+    }));
+    let cull = take_arg(&mut args, "--cull").map(|name| cull::Cull::parse(&name).unwrap_or_else(|| {
+        usage_exit(&format!("unknown cull mode: {}", name))
+    }));
+    let winding = take_arg(&mut args, "--winding").map(|name| cull::Winding::parse(&name).unwrap_or_else(|| {
+        usage_exit(&format!("unknown winding: {}", name))
+    }));
+    let show_back_faces = take_flag(&mut args, "--show-back-faces");
+    let timing = timing::take_args(&mut args).unwrap_or_else(|e| usage_exit(&e));
+    let headless = output::parse_args(&args).unwrap_or_else(|e| usage_exit(&e));
+
+    let clip = hand_clip(interp, mode);
+    let lighting = Lighting::studio(shading);
+    let mut culling = Culling {show_back_faces, ..Culling::default()};
+    if let Some(cull) = cull {
+        culling.cull = cull;
+    }
+    if let Some(winding) = winding {
+        culling.winding = winding;
+    }
+    let mut camera = Camera::default();
+    if let Some((yaw, pitch)) = view {
+        camera.orbit(yaw, pitch);
+    }
+    let hand_file = std::path::Path::new(&hand_file);
+    let mut watcher = model::ModelWatcher::new(hand_file);
+    let model::Model {mesh: mut hand, mut skeleton} = model::load_model(hand_file).unwrap_or_else(|e| {
+        eprintln!("Couldn't load the hand: {}", e);
+        std::process::exit(1);
+    });
+    if let Some(pose) = pose {
+        skeleton.set_pose(pose);
+    }
 
-        let finger_3_top: Vec<_> = hand_triangles[72..84].to_vec();
-        let finger_3_top = rotate_triangles(finger_3_top, 0.0, 0.0, 1.5 * angle);
-        for (i, tri) in finger_3_top.into_iter().enumerate() {
-            hand_triangles[72 + i] = tri;
-        }
+    // the finger animation only makes sense for the hand, and a pose holds it still
+    let animate = model.is_none() && pose.is_none();
+    let watch = model.is_none();
+    if let Some(path) = model {
+        let (triangles, parts) = load_mesh(std::path::Path::new(&path)).unwrap_or_else(|e| {
+            eprintln!("Couldn't load the model: {}", e);
+            std::process::exit(1);
+        });
+        let triangles = match orient {
+            Some(q) => rotate_triangles_quat(triangles, q),
+            None => triangles,
+        };
+        hand = Mesh::new(fit_triangles(triangles, 100.0), parts);
+        skeleton = Skeleton::new();
+    }
+    retexture(&mut hand, texture.as_ref(), filter, wrap, anisotropy);
+
+    if let Some(path) = export {
+        let posed = skeleton.skin(&hand);
+        let path = std::path::Path::new(&path);
+        let written = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("stl") => {
+                let format = if ascii {stl::StlFormat::Ascii} else {stl::StlFormat::Binary};
+                stl::save_stl(path, &posed.triangles, format)
+            }
+            Some("obj") => obj::save_obj(path, &posed.triangles, posed.parts(), !no_mtl),
+            _ => Err(format!("{}: can only export .stl or .obj", path.display())),
+        };
+        written.unwrap_or_else(|e| {
+            eprintln!("Couldn't export: {}", e);
+            std::process::exit(1);
+        });
+        println!("wrote {} triangles to {}", posed.triangles.len(), path.display());
+        return;
+    }
 
-        let finger_4_top: Vec<_> = hand_triangles[96..108].to_vec();
-        let finger_4_top = rotate_triangles(finger_4_top, 0.0, 0.0,  angle);
-        for (i, tri) in finger_4_top.into_iter().enumerate() {
-            hand_triangles[96 + i] = tri;
-        }
+    let mut stepped = timing.fixed_step.map(SteppedClip::new);
 
-        // bottoms
-        let finger_1_bot: Vec<_> = hand_triangles[36..48].to_vec();
-        let finger_1_bot = rotate_triangles(finger_1_bot, 0.0, 0.0, angle);
-        for (i, tri) in finger_1_bot.into_iter().enumerate() {
-            hand_triangles[36 + i] = tri;
+    // no window at all, just dump the frames and leave
+    if let Some(settings) = headless {
+        let mut clock = FrameClock::simulated(HEADLESS_FPS);
+        for frame in 0..settings.frames {
+            match &stepped {
+                Some(stepped) if animate => stepped.pose(&mut skeleton, &clip),
+                None if animate => animate_hand(&mut skeleton, &clip, clock.time),
+                _ => {}
+            }
+            let mut buffer = render_hand(&skeleton.skin(&hand).triangles, &camera, &lighting, &culling, wireframe.then_some(lines));
+            if bones {
+                draw_skeleton(&mut buffer, &camera, &skeleton, lines);
+            }
+            let path = output::write_frame(&settings, frame, &buffer, WIDTH, HEIGHT).unwrap_or_else(|e| {
+                eprintln!("Couldn't write frame {}: {}", frame, e);
+                std::process::exit(1);
+            });
+            println!("wrote {}", path.display());
+
+            clock.tick();
+            if let Some(stepped) = &mut stepped {
+                stepped.advance(clock.dt);
+            }
+            if let (true, Some(report)) = (timing.stats, clock.report()) {
+                println!("{}", report);
+            }
         }
+        return;
+    }
 
-        let finger_2_bot: Vec<_> = hand_triangles[60..72].to_vec();
-        let finger_2_bot = rotate_triangles(finger_2_bot, 0.0, 0.0, angle);
-        for (i, tri) in finger_2_bot.into_iter().enumerate() {
-            hand_triangles[60 + i] = tri;
-        }
+    let mut window = Window::new("Baby Steps", WIDTH, HEIGHT, WindowOptions::default())
+        .unwrap_or_else(|e| panic!("Error making a window! You goofed!: {}", e));
 
-        let finger_3_bot: Vec<_> = hand_triangles[84..96].to_vec();
-        let finger_3_bot = rotate_triangles(finger_3_bot, 0.0, 0.0, angle);
-        for (i, tri) in finger_3_bot.into_iter().enumerate() {
-            hand_triangles[84 + i] = tri;
-        }
+    // the clip runs on the wall clock (or whole --fixed-step steps of it), a slow frame just skips ahead
+    let mut clock = FrameClock::new(timing.target_fps);
+    let mut next_reload = RELOAD_EVERY;
 
-        let finger_4_bot: Vec<_> = hand_triangles[108..120].to_vec();
-        let finger_4_bot = rotate_triangles(finger_4_bot, 0.0, 0.0, angle);
-        for (i, tri) in finger_4_bot.into_iter().enumerate() {
-            hand_triangles[108 + i] = tri;
+    while window.is_open() && !window.is_key_down(Key::Q) {
+        camera.handle_input(&window, clock.dt);
+        // a broken save keeps the last hand that worked on screen
+        if watch && clock.time >= next_reload {
+            next_reload = clock.time + RELOAD_EVERY;
+            match watcher.poll() {
+                Some(Ok(reloaded)) => {
+                    println!("reloaded {}", hand_file.display());
+                    (hand, skeleton) = (reloaded.mesh, reloaded.skeleton);
+                    retexture(&mut hand, texture.as_ref(), filter, wrap, anisotropy);
+                    if let Some(pose) = pose {
+                        skeleton.set_pose(pose);
+                    }
+                }
+                Some(Err(e)) => eprintln!("Couldn't reload the hand: {}", e),
+                None => {}
+            }
         }
-
-        // palm
-        let palm: Vec<_> = hand_triangles[120..132].to_vec();
-        let palm = rotate_triangles(palm, 0.0, 0.0, angle / 2.5);
-        for (i, tri) in palm.into_iter().enumerate() {
-            hand_triangles[120 + i] = tri;
+        match &stepped {
+            Some(stepped) if animate => stepped.pose(&mut skeleton, &clip),
+            None if animate => animate_hand(&mut skeleton, &clip, clock.time),
+            _ => {}
+        }
+        let mut buffer = render_hand(&skeleton.skin(&hand).triangles, &camera, &lighting, &culling, wireframe.then_some(lines));
+        if bones {
+            draw_skeleton(&mut buffer, &camera, &skeleton, lines);
         }
-
-        // hand_triangles = rotate_triangles(hand_triangles.clone(),  angle, -1.0 * angle, -0.5 * angle);
-        draw_3d_from_triangles(&mut buffer, hand_triangles.clone());
-        
-        angle += (dir * 0.00001);
-        
-        if angle > 0.001 {dir = -1.0; }
-        if angle <= -0.00101 {dir = 1.0; }
         
         window.update_with_buffer(&buffer, WIDTH, HEIGHT).unwrap();
+
+        clock.tick();
+        if let Some(stepped) = &mut stepped {
+            stepped.advance(clock.dt);
+        }
+        if let (true, Some(report)) = (timing.stats, clock.report()) {
+            println!("{}", report);
+        }
     }
-}
\ No newline at end of file
+}
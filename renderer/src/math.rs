@@ -0,0 +1,284 @@
+// vectors and 4x4 matrices, every transform in the renderer goes through here
+// conventions, so nobody has to guess:
+//   - x right, y up, z away from the camera (left handed)
+//   - matrices are row major and multiply column vectors, m * v
+//   - a * b applies b first, then a
+//   - euler angles go x then y then z, same as the old rotate_point did
+// the vectors and rotations live in base.rs, which spinning_cube pulls in on
+// its own, everything past that is only the renderer's
+
+use std::ops::{Add, Mul, Sub};
+
+mod base;
+
+pub use base::{Mat4, V3};
+
+// texture coordinates, u across and v up the image
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct V2 {pub x: f64, pub y: f64}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct V4 {pub x: f64, pub y: f64, pub z: f64, pub w: f64}
+
+impl V3 {
+    pub const ZERO: V3 = V3 {x: 0.0, y: 0.0, z: 0.0};
+
+    pub fn dot(self, o: V3) -> f64 {
+        self.x * o.x + self.y * o.y + self.z * o.z
+    }
+
+    pub fn cross(self, o: V3) -> V3 {
+        V3 {
+            x: self.y * o.z - self.z * o.y,
+            y: self.z * o.x - self.x * o.z,
+            z: self.x * o.y - self.y * o.x,
+        }
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    // zero stays zero instead of turning into NaNs
+    pub fn normalize(self) -> V3 {
+        let len = self.length();
+        if len == 0.0 {self} else {self / len}
+    }
+
+    pub fn lerp(self, o: V3, t: f64) -> V3 {
+        self + (o - self) * t
+    }
+
+    pub fn min(self, o: V3) -> V3 {
+        V3 {x: self.x.min(o.x), y: self.y.min(o.y), z: self.z.min(o.z)}
+    }
+
+    pub fn max(self, o: V3) -> V3 {
+        V3 {x: self.x.max(o.x), y: self.y.max(o.y), z: self.z.max(o.z)}
+    }
+}
+
+impl V2 {
+    pub const ZERO: V2 = V2 {x: 0.0, y: 0.0};
+
+    pub const fn new(x: f64, y: f64) -> V2 {
+        V2 {x, y}
+    }
+
+    pub fn lerp(self, o: V2, t: f64) -> V2 {
+        self + (o - self) * t
+    }
+}
+
+impl Add for V2 {
+    type Output = V2;
+    fn add(self, o: V2) -> V2 {V2 {x: self.x + o.x, y: self.y + o.y}}
+}
+
+impl Sub for V2 {
+    type Output = V2;
+    fn sub(self, o: V2) -> V2 {V2 {x: self.x - o.x, y: self.y - o.y}}
+}
+
+impl Mul<f64> for V2 {
+    type Output = V2;
+    fn mul(self, s: f64) -> V2 {V2 {x: self.x * s, y: self.y * s}}
+}
+
+impl V4 {
+    pub fn lerp(self, o: V4, t: f64) -> V4 {
+        V4 {
+            x: self.x + (o.x - self.x) * t,
+            y: self.y + (o.y - self.y) * t,
+            z: self.z + (o.z - self.z) * t,
+            w: self.w + (o.w - self.w) * t,
+        }
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {m: [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]};
+
+    pub fn translate(t: V3) -> Mat4 {
+        Mat4 {m: [
+            [1.0, 0.0, 0.0, t.x],
+            [0.0, 1.0, 0.0, t.y],
+            [0.0, 0.0, 1.0, t.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ]}
+    }
+
+    pub fn scale(s: V3) -> Mat4 {
+        Mat4 {m: [
+            [s.x, 0.0, 0.0, 0.0],
+            [0.0, s.y, 0.0, 0.0],
+            [0.0, 0.0, s.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]}
+    }
+
+    // rotation around some point other than the origin
+    pub fn rotate_around(angles: V3, pivot: V3) -> Mat4 {
+        Mat4::translate(pivot) * Mat4::rotate(angles) * Mat4::translate(-pivot)
+    }
+
+    // camera space -> clip space, w ends up holding the camera space z
+    // which is what the clipper and the depth buffer want
+    pub fn perspective(fov: f64, aspect: f64, near: f64, far: f64) -> Mat4 {
+        let f = 1.0 / (fov * 0.5).tan();
+        Mat4 {m: [
+            [f / aspect, 0.0, 0.0, 0.0],
+            [0.0, f, 0.0, 0.0],
+            [0.0, 0.0, (far + near) / (far - near), -2.0 * far * near / (far - near)],
+            [0.0, 0.0, 1.0, 0.0],
+        ]}
+    }
+
+    // world space -> camera space for a camera at eye looking at target
+    pub fn look_at(eye: V3, target: V3, up: V3) -> Mat4 {
+        let z = (target - eye).normalize();
+        let x = up.cross(z).normalize();
+        let y = z.cross(x);
+        Mat4 {m: [
+            [x.x, x.y, x.z, -x.dot(eye)],
+            [y.x, y.y, y.z, -y.dot(eye)],
+            [z.x, z.y, z.z, -z.dot(eye)],
+            [0.0, 0.0, 0.0, 1.0],
+        ]}
+    }
+
+    // direction, ignores the translation
+    pub fn transform_dir(&self, v: V3) -> V3 {
+        let m = &self.m;
+        V3 {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        }
+    }
+
+    // the rows of the 3x3 rotate/scale part
+    fn rows3(&self) -> [V3; 3] {
+        let row = |r: usize| V3::new(self.m[r][0], self.m[r][1], self.m[r][2]);
+        [row(0), row(1), row(2)]
+    }
+
+    // determinant of the 3x3 part, negative when the matrix mirrors things
+    pub fn det3(&self) -> f64 {
+        let [a, b, c] = self.rows3();
+        a.dot(b.cross(c))
+    }
+
+    // what normals go through: the inverse transpose of the 3x3 part, so
+    // they stay at right angles to a surface that got squashed or stretched
+    // (transform_dir would tilt them along with the surface)
+    // it's the cofactors, which is that times the determinant, with only the
+    // sign of that kept since normals get normalized anyway
+    pub fn normal_matrix(&self) -> Mat4 {
+        let [a, b, c] = self.rows3();
+        let sign = if self.det3() < 0.0 {-1.0} else {1.0};
+        let rows = [b.cross(c) * sign, c.cross(a) * sign, a.cross(b) * sign];
+        let mut out = Mat4::IDENTITY;
+        for (r, row) in rows.iter().enumerate() {
+            out.m[r][..3].copy_from_slice(&[row.x, row.y, row.z]);
+        }
+        out
+    }
+
+    // the full thing with w = 1, for going to clip space
+    pub fn transform_v4(&self, v: V3) -> V4 {
+        let m = &self.m;
+        V4 {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z + m[0][3],
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z + m[1][3],
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z + m[2][3],
+            w: m[3][0] * v.x + m[3][1] * v.y + m[3][2] * v.z + m[3][3],
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: V3, b: V3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn vector_basics() {
+        let x = V3::new(1.0, 0.0, 0.0);
+        let y = V3::new(0.0, 1.0, 0.0);
+        assert_eq!(x.cross(y), V3::new(0.0, 0.0, 1.0));
+        assert_eq!(x.dot(y), 0.0);
+        assert_eq!(V3::new(3.0, 4.0, 0.0).length(), 5.0);
+        assert!(close(V3::new(0.0, 0.0, 7.0).normalize(), V3::new(0.0, 0.0, 1.0)));
+        assert_eq!(V3::ZERO.normalize(), V3::ZERO);
+    }
+
+    #[test]
+    fn euler_order_is_x_then_y_then_z() {
+        // the formula rotate_point used to have, written out longhand
+        let (i, j, k) = (3.0, -2.0, 5.0);
+        let (ax, ay, az): (f64, f64, f64) = (0.3, -1.1, 2.0);
+        let (x, y, z) = (i, j * ax.cos() - k * ax.sin(), j * ax.sin() + k * ax.cos());
+        let (nx, ny, nz) = (x * ay.cos() + z * ay.sin(), y, -x * ay.sin() + z * ay.cos());
+        let expected = V3::new(nx * az.cos() - ny * az.sin(), nx * az.sin() + ny * az.cos(), nz);
+
+        let got = Mat4::rotate(V3::new(ax, ay, az)).transform_point(V3::new(i, j, k));
+        assert!(close(got, expected), "{:?} vs {:?}", got, expected);
+    }
+
+    #[test]
+    fn normals_stay_perpendicular() {
+        // a 45 degree slope squashed to half height gets shallower, its
+        // normal has to lean further up to stay at right angles to it
+        let squash = Mat4::rotate_z(0.3) * Mat4::scale(V3::new(1.0, 0.5, 1.0));
+        let (along, normal) = (V3::new(1.0, 1.0, 0.0), V3::new(-1.0, 1.0, 0.0));
+        let n = squash.normal_matrix().transform_dir(normal);
+        assert!(n.dot(squash.transform_dir(along)).abs() < 1e-9);
+        assert!(n.dot(squash.transform_dir(normal)) > 0.0);
+
+        // mirroring keeps them pointing the way the mirrored surface faces
+        let mirror = Mat4::scale(V3::new(-1.0, 2.0, 1.0));
+        assert!(mirror.det3() < 0.0);
+        assert!(close(mirror.normal_matrix().transform_dir(V3::new(1.0, 0.0, 0.0)).normalize(), V3::new(-1.0, 0.0, 0.0)));
+        assert_eq!(Mat4::translate(V3::new(1.0, 2.0, 3.0)).normal_matrix(), Mat4::IDENTITY);
+    }
+
+    #[test]
+    fn composition_applies_right_to_left() {
+        let m = Mat4::translate(V3::new(10.0, 0.0, 0.0)) * Mat4::rotate_z(std::f64::consts::FRAC_PI_2);
+        assert!(close(m.transform_point(V3::new(1.0, 0.0, 0.0)), V3::new(10.0, 1.0, 0.0)));
+        assert!(close(m.transform_dir(V3::new(1.0, 0.0, 0.0)), V3::new(0.0, 1.0, 0.0)));
+
+        let pivot = V3::new(5.0, 5.0, 0.0);
+        assert!(close(Mat4::rotate_around(V3::new(0.0, 0.0, 1.0), pivot).transform_point(pivot), pivot));
+        assert_eq!(Mat4::scale(V3::new(2.0, 3.0, 4.0)).transform_point(V3::new(1.0, 1.0, 1.0)), V3::new(2.0, 3.0, 4.0));
+    }
+
+    #[test]
+    fn look_at_and_perspective() {
+        // the default camera: 150 back, looking down +z
+        let view = Mat4::look_at(V3::new(0.0, 0.0, -150.0), V3::ZERO, V3::new(0.0, 1.0, 0.0));
+        assert!(close(view.transform_point(V3::new(1.0, 2.0, 3.0)), V3::new(1.0, 2.0, 153.0)));
+
+        // looking down +x instead, what's ahead ends up at +z
+        let view = Mat4::look_at(V3::ZERO, V3::new(1.0, 0.0, 0.0), V3::new(0.0, 1.0, 0.0));
+        assert!(close(view.transform_point(V3::new(5.0, 0.0, 0.0)), V3::new(0.0, 0.0, 5.0)));
+
+        let proj = Mat4::perspective(90.0f64.to_radians(), 1.0, 0.1, 1000.0);
+        let near = proj.transform_v4(V3::new(0.0, 0.0, 0.1));
+        let far = proj.transform_v4(V3::new(0.0, 0.0, 1000.0));
+        assert!((near.z / near.w + 1.0).abs() < 1e-9);
+        assert!((far.z / far.w - 1.0).abs() < 1e-9);
+        let edge = proj.transform_v4(V3::new(10.0, 0.0, 10.0));
+        assert!((edge.x / edge.w - 1.0).abs() < 1e-9); // 45 degrees off axis is the edge of the screen
+    }
+}
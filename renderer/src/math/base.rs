@@ -0,0 +1,111 @@
+// the part of math.rs spinning_cube shares: V3 with its operators and Mat4
+// rotations, and nothing it doesn't use so it can pull this file in as is
+// it can't lean on anything in the crate either
+// math.rs adds everything else onto the same types
+
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct V3 {pub x: f64, pub y: f64, pub z: f64}
+
+impl V3 {
+    pub const fn new(x: f64, y: f64, z: f64) -> V3 {
+        V3 {x, y, z}
+    }
+}
+
+impl Add for V3 {
+    type Output = V3;
+    fn add(self, o: V3) -> V3 {V3 {x: self.x + o.x, y: self.y + o.y, z: self.z + o.z}}
+}
+
+impl Sub for V3 {
+    type Output = V3;
+    fn sub(self, o: V3) -> V3 {V3 {x: self.x - o.x, y: self.y - o.y, z: self.z - o.z}}
+}
+
+impl Mul<f64> for V3 {
+    type Output = V3;
+    fn mul(self, s: f64) -> V3 {V3 {x: self.x * s, y: self.y * s, z: self.z * s}}
+}
+
+impl Div<f64> for V3 {
+    type Output = V3;
+    fn div(self, s: f64) -> V3 {V3 {x: self.x / s, y: self.y / s, z: self.z / s}}
+}
+
+impl Neg for V3 {
+    type Output = V3;
+    fn neg(self) -> V3 {V3 {x: -self.x, y: -self.y, z: -self.z}}
+}
+
+impl AddAssign for V3 {
+    fn add_assign(&mut self, o: V3) {*self = *self + o;}
+}
+
+impl SubAssign for V3 {
+    fn sub_assign(&mut self, o: V3) {*self = *self - o;}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4 {pub m: [[f64; 4]; 4]}
+
+impl Mat4 {
+    pub fn rotate_x(a: f64) -> Mat4 {
+        let (s, c) = a.sin_cos();
+        Mat4 {m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, c, -s, 0.0],
+            [0.0, s, c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]}
+    }
+
+    pub fn rotate_y(a: f64) -> Mat4 {
+        let (s, c) = a.sin_cos();
+        Mat4 {m: [
+            [c, 0.0, s, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-s, 0.0, c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]}
+    }
+
+    pub fn rotate_z(a: f64) -> Mat4 {
+        let (s, c) = a.sin_cos();
+        Mat4 {m: [
+            [c, -s, 0.0, 0.0],
+            [s, c, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]}
+    }
+
+    // x first, then y, then z
+    pub fn rotate(angles: V3) -> Mat4 {
+        Mat4::rotate_z(angles.z) * Mat4::rotate_y(angles.y) * Mat4::rotate_x(angles.x)
+    }
+
+    // position, gets the translation
+    pub fn transform_point(&self, v: V3) -> V3 {
+        let m = &self.m;
+        V3 {
+            x: m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z + m[0][3],
+            y: m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z + m[1][3],
+            z: m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z + m[2][3],
+        }
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+    fn mul(self, o: Mat4) -> Mat4 {
+        let mut out = Mat4 {m: [[0.0; 4]; 4]};
+        for r in 0..4 {
+            for c in 0..4 {
+                out.m[r][c] = (0..4).map(|k| self.m[r][k] * o.m[k][c]).sum();
+            }
+        }
+        out
+    }
+}
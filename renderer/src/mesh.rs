@@ -0,0 +1,164 @@
+// a bag of triangles plus names for slices of it, so code can say
+// "rotate finger_2_top" instead of "rotate hand_triangles[48..60]" and
+// not break the moment somebody adds a part in front of it
+
+use std::ops::Range;
+
+use crate::math::Mat4;
+#[cfg(test)]
+use crate::math::V3;
+use crate::{transform_triangle, Triangle3d};
+
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    pub triangles: Vec<Triangle3d>,
+    parts: Vec<(String, usize, usize)>,
+}
+
+impl Mesh {
+    // parts are the (name, start, end) ranges the model and obj loaders hand back
+    pub fn new(triangles: Vec<Triangle3d>, parts: Vec<(String, usize, usize)>) -> Mesh {
+        for (i, (name, start, end)) in parts.iter().enumerate() {
+            assert!(start <= end && *end <= triangles.len(),
+                    "part {} ({}..{}) is outside the mesh ({} triangles)", name, start, end, triangles.len());
+            assert!(parts[..i].iter().all(|p| p.0 != *name), "part {} is in the mesh twice", name);
+        }
+        Mesh {triangles, parts}
+    }
+
+    // the hand moves through the skeleton, so looking at parts one by one and
+    // moving them by hand is only something the tests do
+    #[cfg(test)]
+    pub fn part_names(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().map(|p| p.0.as_str())
+    }
+
+    // the (name, start, end) ranges, as they went in
+    pub fn parts(&self) -> &[(String, usize, usize)] {
+        &self.parts
+    }
+
+    pub fn part_range(&self, name: &str) -> Option<Range<usize>> {
+        self.parts.iter().find(|p| p.0 == name).map(|p| p.1..p.2)
+    }
+
+    #[cfg(test)]
+    pub fn part(&self, name: &str) -> Option<&[Triangle3d]> {
+        let range = self.part_range(name)?;
+        Some(&self.triangles[range])
+    }
+
+    pub fn part_mut(&mut self, name: &str) -> Option<&mut [Triangle3d]> {
+        let range = self.part_range(name)?;
+        Some(&mut self.triangles[range])
+    }
+
+    // axis aligned box around a part, handy for finding where joints go
+    #[cfg(test)]
+    pub fn part_bounds(&self, name: &str) -> Option<(V3, V3)> {
+        let part = self.part(name)?;
+        let mut min = V3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = V3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for tri in part {
+            for v in [tri.v0, tri.v1, tri.v2] {
+                min = min.min(v);
+                max = max.max(v);
+            }
+        }
+        Some((min, max))
+    }
+
+    // run every vertex of a part through f, only the positions, the normals
+    // stay put so use transform_part_by for anything that gets lit
+    // asking for a part that isn't there is a bug in the caller, so it panics
+    // instead of quietly animating nothing
+    #[cfg(test)]
+    pub fn transform_part(&mut self, name: &str, f: impl Fn(V3) -> V3) {
+        let part = self.part_mut(name).unwrap_or_else(|| panic!("the mesh has no part named {}", name));
+        for tri in part {
+            tri.v0 = f(tri.v0);
+            tri.v1 = f(tri.v1);
+            tri.v2 = f(tri.v2);
+        }
+    }
+
+    // positions and normals both
+    pub fn transform_part_by(&mut self, name: &str, m: &Mat4) {
+        let part = self.part_mut(name).unwrap_or_else(|| panic!("the mesh has no part named {}", name));
+        for tri in part {
+            transform_triangle(tri, m);
+        }
+    }
+
+    // same rotation as rotate_triangles, x then y then z around the origin
+    #[cfg(test)]
+    pub fn rotate_part(&mut self, name: &str, ax: f64, ay: f64, az: f64) {
+        self.transform_part_by(name, &Mat4::rotate(V3::new(ax, ay, az)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_box_triangles, WHITE};
+
+    fn two_boxes() -> Mesh {
+        let mut triangles = get_box_triangles(V3::ZERO, V3::new(1.0, 1.0, 1.0), [WHITE; 6]);
+        triangles.extend(get_box_triangles(V3::new(10.0, 0.0, 0.0), V3::new(2.0, 3.0, 4.0), [WHITE; 6]));
+        Mesh::new(triangles, vec![("a".into(), 0, 12), ("b".into(), 12, 24)])
+    }
+
+    #[test]
+    fn parts_by_name() {
+        let mesh = two_boxes();
+        assert_eq!(mesh.part_names().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(mesh.part_range("b"), Some(12..24));
+        assert_eq!(mesh.part("b").unwrap().len(), 12);
+        assert_eq!(mesh.part("b").unwrap()[0].v0, mesh.triangles[12].v0);
+        assert!(mesh.part("c").is_none());
+        assert!(mesh.part_bounds("c").is_none());
+
+        assert_eq!(mesh.part_bounds("a"), Some((V3::new(-1.0, -1.0, -1.0), V3::new(1.0, 1.0, 1.0))));
+        assert_eq!(mesh.part_bounds("b"), Some((V3::new(8.0, -3.0, -4.0), V3::new(12.0, 3.0, 4.0))));
+    }
+
+    #[test]
+    fn transforming_a_part_leaves_the_rest() {
+        let rest = two_boxes();
+        let mut mesh = rest.clone();
+        mesh.transform_part("a", |v| v + V3::new(0.0, 5.0, 0.0));
+        assert_eq!(mesh.part_bounds("a"), Some((V3::new(-1.0, 4.0, -1.0), V3::new(1.0, 6.0, 1.0))));
+        assert_eq!(mesh.part_bounds("b"), rest.part_bounds("b"));
+        // only the positions
+        for (a, b) in mesh.triangles.iter().zip(&rest.triangles) {
+            assert_eq!((a.n0, a.n1, a.n2), (b.n0, b.n1, b.n2));
+        }
+
+        // a quarter turn round z takes the normals along with it
+        mesh.rotate_part("b", 0.0, 0.0, std::f64::consts::FRAC_PI_2);
+        let (min, max) = mesh.part_bounds("b").unwrap();
+        assert!((min - V3::new(-3.0, 8.0, -4.0)).length() < 1e-9 && (max - V3::new(3.0, 12.0, 4.0)).length() < 1e-9);
+        let tri = &mesh.part("b").unwrap()[0];
+        assert!(tri.n0.dot(tri.face_normal()) > 0.999);
+    }
+
+    #[test]
+    #[should_panic(expected = "no part named c")]
+    fn transforming_a_missing_part_panics() {
+        two_boxes().transform_part("c", |v| v);
+    }
+
+    #[test]
+    #[should_panic(expected = "in the mesh twice")]
+    fn part_names_are_unique() {
+        let triangles = get_box_triangles(V3::ZERO, V3::new(1.0, 1.0, 1.0), [WHITE; 6]);
+        Mesh::new(triangles, vec![("a".into(), 0, 6), ("a".into(), 6, 12)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "outside the mesh")]
+    fn parts_stay_inside_the_mesh() {
+        let triangles = get_box_triangles(V3::ZERO, V3::new(1.0, 1.0, 1.0), [WHITE; 6]);
+        Mesh::new(triangles, vec![("a".into(), 6, 13)]);
+    }
+}
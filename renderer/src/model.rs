@@ -0,0 +1,269 @@
+// composite models described in a toml file instead of in code: a list of
+// named parts (boxes, spheres, ...) with where they go and what color they
+// are, and the joints that move them
+// models/hand.toml is the hand, the comments at the top of it explain the fields
+//
+// ModelWatcher keeps an eye on the file so edits show up without a restart
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use serde::Deserialize;
+
+use crate::light::Material;
+use crate::math::{Mat4, V3};
+use crate::mesh::Mesh;
+use crate::skeleton::Skeleton;
+use crate::texture::{Filter, Texture, Wrap, MAX_ANISOTROPY};
+use crate::{get_box_triangles, primitives, transform_triangles};
+
+// how finely the round shapes get cut up
+const SEGMENTS: usize = 16;
+const RINGS: usize = 6;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModelFile {
+    #[serde(default)]
+    part: Vec<PartDesc>,
+    #[serde(default)]
+    joint: Vec<JointDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PartDesc {
+    name: String,
+    #[serde(default = "default_shape")]
+    shape: String,
+    center: [f64; 3],
+    size: [f64; 3],
+    color: String,
+    joint: Option<String>,
+    #[serde(default = "default_material")]
+    material: String,
+    texture: Option<String>,
+    filter: Option<String>,
+    wrap: Option<String>,
+    anisotropy: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JointDesc {
+    name: String,
+    parent: Option<String>,
+    pivot: [f64; 3],
+    #[serde(default)]
+    min: [f64; 3],
+    #[serde(default)]
+    max: [f64; 3],
+}
+
+fn default_shape() -> String {
+    "box".to_string()
+}
+
+fn default_material() -> String {
+    "plastic".to_string()
+}
+
+fn v3(a: [f64; 3]) -> V3 {
+    V3::new(a[0], a[1], a[2])
+}
+
+#[derive(Clone, Debug)]
+pub struct Model {
+    pub mesh: Mesh,
+    pub skeleton: Skeleton,
+}
+
+pub fn load_model(path: &Path) -> Result<Model, String> {
+    let src = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse_model(&src, path.parent().unwrap_or(Path::new(".")))
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// "#RRGGBB" -> 0xRRGGBB
+fn parse_color(s: &str) -> Option<u32> {
+    let hex = s.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
+
+// texture paths are relative to `dir`
+pub fn parse_model(src: &str, dir: &Path) -> Result<Model, String> {
+    let file: ModelFile = toml::from_str(src).map_err(|e| e.to_string())?;
+
+    // joints first so the parts can be checked against them, add_joint and
+    // bind panic on names they don't know and a typo in a file isn't a bug
+    let mut skeleton = Skeleton::new();
+    for j in &file.joint {
+        if skeleton.index(&j.name).is_some() {
+            return Err(format!("joint {} is in the file twice", j.name));
+        }
+        if let Some(parent) = j.parent.as_ref().filter(|p| skeleton.index(p).is_none()) {
+            return Err(format!("joint {} needs its parent {} listed before it", j.name, parent));
+        }
+        // clamping between them panics otherwise
+        if let Some(axis) = (0..3).find(|&i| !j.min[i].is_finite() || !j.max[i].is_finite() || j.min[i] > j.max[i]) {
+            return Err(format!("joint {}: {} limits {} to {} don't make a range", j.name, ["x", "y", "z"][axis], j.min[axis], j.max[axis]));
+        }
+        skeleton.add_joint(&j.name, j.parent.as_deref(), v3(j.pivot), v3(j.min), v3(j.max));
+    }
+
+    let mut triangles = vec![];
+    let mut parts = vec![];
+    // parts sharing an image share the one copy
+    let mut textures: HashMap<&str, Arc<Texture>> = HashMap::new();
+    for p in &file.part {
+        if parts.iter().any(|(name, _, _)| name == &p.name) {
+            return Err(format!("part {} is in the file twice", p.name));
+        }
+        let color = parse_color(&p.color)
+            .ok_or_else(|| format!("part {}: bad color {:?}, expected \"#RRGGBB\"", p.name, p.color))?;
+        let mut material = match p.material.as_str() {
+            "plastic" => Material::plastic(color),
+            "matte" => Material::matte(color),
+            other => return Err(format!("part {}: unknown material {}", p.name, other)),
+        };
+        if let Some(file) = &p.texture {
+            let texture = match textures.get(file.as_str()) {
+                Some(texture) => texture.clone(),
+                None => Arc::new(Texture::load(&dir.join(file)).map_err(|e| format!("part {}: {}", p.name, e))?),
+            };
+            textures.insert(file, texture.clone());
+            material.texture = Some(texture);
+        }
+        if let Some(filter) = &p.filter {
+            material.sampler.filter = Filter::parse(filter)
+                .ok_or_else(|| format!("part {}: unknown filter {}, expected nearest, bilinear or trilinear", p.name, filter))?;
+        }
+        if let Some(wrap) = &p.wrap {
+            material.sampler.wrap = Wrap::parse(wrap)
+                .ok_or_else(|| format!("part {}: unknown wrap {}, expected repeat or clamp", p.name, wrap))?;
+        }
+        if let Some(anisotropy) = p.anisotropy {
+            if !(1..=MAX_ANISOTROPY).contains(&anisotropy) {
+                return Err(format!("part {}: anisotropy {} isn't 1 to {}", p.name, anisotropy, MAX_ANISOTROPY));
+            }
+            material.sampler.anisotropy = anisotropy;
+        }
+
+        let [sx, sy, sz] = p.size;
+        let shape = match p.shape.as_str() {
+            "box" => get_box_triangles(V3::ZERO, v3(p.size), [color; 6]),
+            "sphere" => primitives::sphere(sx, SEGMENTS, RINGS * 2, color),
+            "cylinder" => primitives::cylinder(sx, sy * 2.0, SEGMENTS, true, color),
+            "capsule" => primitives::capsule(sx, sy * 2.0, SEGMENTS, RINGS, color),
+            "cone" => primitives::cone(sx, sy * 2.0, SEGMENTS, color),
+            "torus" => primitives::torus(sx, sy, SEGMENTS * 2, SEGMENTS, color),
+            "plane" => primitives::plane(sx * 2.0, sz * 2.0, 1, 1, color),
+            other => return Err(format!("part {}: unknown shape {}", p.name, other)),
+        };
+        // the round ones are sized by x and y, z only stretches them if it's different
+        let flat = p.shape == "box" || p.shape == "plane";
+        let stretch = if flat || sz == 0.0 || sz == sx {1.0} else {sz / sx};
+        let place = Mat4::translate(v3(p.center)) * Mat4::scale(V3::new(1.0, 1.0, stretch));
+        let mut shape = transform_triangles(shape, &place);
+        for tri in &mut shape {
+            tri.set_material(material.clone());
+        }
+
+        let start = triangles.len();
+        triangles.extend(shape);
+        parts.push((p.name.clone(), start, triangles.len()));
+
+        if let Some(joint) = &p.joint {
+            if skeleton.index(joint).is_none() {
+                return Err(format!("part {}: no joint named {}", p.name, joint));
+            }
+            skeleton.bind(&p.name, joint);
+        }
+    }
+
+    Ok(Model {mesh: Mesh::new(triangles, parts), skeleton})
+}
+
+// reloads the file whenever its modification time changes
+pub struct ModelWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ModelWatcher {
+    pub fn new(path: &Path) -> ModelWatcher {
+        ModelWatcher {path: path.to_path_buf(), modified: modified(path)}
+    }
+
+    // Some when the file changed since the last look, with the new model or
+    // whatever went wrong reading it
+    pub fn poll(&mut self) -> Option<Result<Model, String>> {
+        let now = modified(&self.path);
+        if now == self.modified {
+            return None;
+        }
+        self.modified = now;
+        Some(load_model(&self.path))
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_hand_file_loads() {
+        let model = load_model(&Path::new(env!("CARGO_MANIFEST_DIR")).join("models/hand.toml")).unwrap();
+        assert_eq!(model.mesh.part_names().count(), 11);
+        assert_eq!(model.skeleton.joints().len(), 10);
+        assert_eq!(model.mesh.part("palm").unwrap()[0].color, 0xFF0000);
+        let (min, max) = model.mesh.part_bounds("finger_4_bot").unwrap();
+        assert!((min.x - 30.4).abs() < 1e-9 && (max.y - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn mistakes_are_errors() {
+        let part = |extra: &str| format!("[[part]]\nname = \"a\"\ncenter = [0, 0, 0]\nsize = [1, 1, 1]\ncolor = \"#FF0000\"\n{}", extra);
+        assert!(parse_model(&part(""), Path::new(".")).is_ok());
+        assert!(parse_model(&part("shape = \"teapot\""), Path::new(".")).unwrap_err().contains("unknown shape"));
+        assert!(parse_model(&part("joint = \"nope\""), Path::new(".")).unwrap_err().contains("no joint named nope"));
+        assert!(parse_model(&part("colour = \"red\""), Path::new(".")).is_err());
+        assert!(parse_model(&part("").replace("#FF0000", "red"), Path::new(".")).unwrap_err().contains("bad color"));
+
+        let joints = "[[joint]]\nname = \"b\"\nparent = \"a\"\npivot = [0, 0, 0]\n[[joint]]\nname = \"a\"\npivot = [0, 0, 0]\n";
+        assert!(parse_model(joints, Path::new(".")).unwrap_err().contains("listed before"));
+        let limits = |extra: &str| format!("[[joint]]\nname = \"a\"\npivot = [0, 0, 0]\n{}", extra);
+        assert!(parse_model(&limits("min = [-1, 0, 0]\nmax = [1, 0, 0]"), Path::new(".")).is_ok());
+        assert!(parse_model(&limits("min = [1, 0, 0]"), Path::new(".")).unwrap_err().contains("joint a: x limits 1 to 0"));
+        assert!(parse_model(&limits("max = [0, 0, nan]"), Path::new(".")).unwrap_err().contains("joint a: z limits"));
+
+        assert!(parse_model(&part("filter = \"blurry\""), Path::new(".")).unwrap_err().contains("unknown filter"));
+        assert!(parse_model(&part("anisotropy = 0"), Path::new(".")).unwrap_err().contains("isn't 1 to 16"));
+        assert!(parse_model(&part("texture = \"nowhere.png\""), Path::new(".")).unwrap_err().contains("nowhere.png"));
+    }
+
+    #[test]
+    fn shapes_fill_their_size() {
+        let bounds = |shape: &str, size: &str| {
+            let src = format!("[[part]]\nname = \"a\"\nshape = \"{}\"\ncenter = [10, 0, 0]\nsize = {}\ncolor = \"#FF0000\"\n", shape, size);
+            parse_model(&src, Path::new(".")).unwrap().mesh.part_bounds("a").unwrap()
+        };
+        let close = |(min, max): (V3, V3), lo: [f64; 3], hi: [f64; 3]| (min - v3(lo)).length() < 1e-9 && (max - v3(hi)).length() < 1e-9;
+        assert!(close(bounds("box", "[1, 2, 3]"), [9.0, -2.0, -3.0], [11.0, 2.0, 3.0]));
+        // x is the radius of the base and y half the height
+        assert!(close(bounds("cone", "[2, 3, 0]"), [8.0, -3.0, -2.0], [12.0, 3.0, 2.0]));
+        // x out to the middle of the tube and y the radius of the tube
+        assert!(close(bounds("torus", "[5, 1, 0]"), [4.0, -1.0, -6.0], [16.0, 1.0, 6.0]));
+        assert!(close(bounds("plane", "[4, 0, 2]"), [6.0, 0.0, -2.0], [14.0, 0.0, 2.0]));
+    }
+}
@@ -0,0 +1,441 @@
+// wavefront .obj loading, so we can draw real models instead of typing
+// out every vertex by hand
+// what we understand: v, vt, f (any number of corners, v/vt/vn style indices,
+// negative indices), g and o for named parts, usemtl + mtllib for materials
+// and map_Kd for their textures
+// everything else (normals, smoothing groups, ...) is skipped for now
+//
+// and writing them back out: positions, uvs, the corner normals, one g per part
+// and the materials in an mtl file next to it, enough for blender or meshlab
+// to show a posed hand the way it looked here
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::light::{color_to_v3, v3_to_color, Material};
+use crate::math::V2;
+use crate::texture::{Texture, Wrap};
+use crate::{get_triangle_from_vecs, Triangle3d, V3};
+
+// named parts use the same (name, start, end) ranges the hand file gives the hand
+pub type Parts = Vec<(String, usize, usize)>;
+
+// adds a part, names have to be unique for Mesh::part to find them so a
+// name that's taken gets a number on the end (a, a_2, a_3, ...)
+pub fn add_part(parts: &mut Parts, base: &str, start: usize, end: usize) {
+    let mut name = base.to_string();
+    let mut n = 2;
+    while parts.iter().any(|(p, _, _)| *p == name) {
+        name = format!("{}_{}", base, n);
+        n += 1;
+    }
+    parts.push((name, start, end));
+}
+
+pub fn load_obj(path: &Path, default_color: u32) -> Result<(Vec<Triangle3d>, Parts), String> {
+    let src = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    // colors come from the material libraries, which live next to the obj
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut materials = HashMap::new();
+    for line in src.lines() {
+        let mut words = line.split_whitespace();
+        if words.next() == Some("mtllib") {
+            for lib in words {
+                let lib_path = dir.join(lib);
+                let lib_src = fs::read_to_string(&lib_path)
+                    .map_err(|e| format!("{}: {}", lib_path.display(), e))?;
+                materials.extend(parse_mtl(&lib_src, dir)
+                    .map_err(|e| format!("{}: {}", lib_path.display(), e))?);
+            }
+        }
+    }
+
+    parse_obj(&src, &materials, default_color).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// the colors (Kd diffuse, Ks specular, Ke emissive), the shininess (Ns) and
+// the diffuse texture (map_Kd, relative to `dir`), the rest of what mtl files
+// can say doesn't apply to us
+pub fn parse_mtl(src: &str, dir: &Path) -> Result<HashMap<String, Material>, String> {
+    let mut out = HashMap::new();
+    let mut current: Option<String> = None;
+
+    for (n, line) in src.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        let Some(key) = words.next() else { continue };
+        if key == "newmtl" {
+            let name = words.next().ok_or(format!("line {}: newmtl without a name", n + 1))?;
+            out.insert(name.to_string(), Material::matte(0xFFFFFF));
+            current = Some(name.to_string());
+            continue;
+        }
+        if key == "map_Kd" {
+            let name = current.as_ref().ok_or(format!("line {}: map_Kd before any newmtl", n + 1))?;
+            // options come before the file name, -clamp is the only one we follow
+            let words: Vec<&str> = words.collect();
+            let file = words.last().ok_or(format!("line {}: map_Kd without a file", n + 1))?;
+            // like an unknown usemtl a texture we can't read (a .jpg, say) isn't
+            // worth failing the whole model over, it just goes without
+            let texture = match Texture::load(&dir.join(file)) {
+                Ok(texture) => texture,
+                Err(e) => {
+                    eprintln!("line {}: leaving {} untextured: {}", n + 1, name, e);
+                    continue;
+                }
+            };
+            let material = out.get_mut(name).unwrap();
+            material.texture = Some(Arc::new(texture));
+            if words.windows(2).any(|w| w == ["-clamp", "on"]) {
+                material.sampler.wrap = Wrap::Clamp;
+            }
+            continue;
+        }
+        if !["Kd", "Ks", "Ke", "Ns"].contains(&key) {
+            continue;
+        }
+
+        let name = current.as_ref().ok_or(format!("line {}: {} before any newmtl", n + 1, key))?;
+        let values = parse_floats(words, n)?;
+        let material = out.get_mut(name).unwrap();
+        if key == "Ns" {
+            material.shininess = *values.first().ok_or(format!("line {}: Ns needs a value", n + 1))?;
+            continue;
+        }
+        if values.len() < 3 {
+            return Err(format!("line {}: {} needs three values", n + 1, key));
+        }
+        // through a u32 so the color is exactly what the triangles get
+        let rgb = color_to_v3(rgb_to_color(values[0], values[1], values[2]));
+        match key {
+            "Kd" => material.diffuse = rgb,
+            "Ks" => material.specular = rgb,
+            _ => material.emissive = rgb,
+        }
+    }
+    Ok(out)
+}
+
+pub fn rgb_to_color(r: f64, g: f64, b: f64) -> u32 {
+    let to_byte = |c: f64| (c.clamp(0.0, 1.0) * 255.0).round() as u32;
+    to_byte(r) << 16 | to_byte(g) << 8 | to_byte(b)
+}
+
+fn parse_floats<'a>(words: impl Iterator<Item = &'a str>, n: usize) -> Result<Vec<f64>, String> {
+    words.map(|w| w.parse::<f64>().map_err(|_| format!("line {}: bad number {:?}", n + 1, w))).collect()
+}
+
+// obj indices start at 1 and negative ones count back from the last one
+fn resolve_index(word: &str, idx: &str, count: usize, what: &str, n: usize) -> Result<usize, String> {
+    let idx: i64 = idx.parse().map_err(|_| format!("line {}: bad face index {:?}", n + 1, word))?;
+    let resolved = if idx < 0 {count as i64 + idx} else {idx - 1};
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("line {}: face index {} out of range ({} {} so far)", n + 1, idx, count, what));
+    }
+    Ok(resolved as usize)
+}
+
+// one corner of a face, v, v/vt, v//vn or v/vt/vn, into the vertex and the uv if it has one
+fn resolve_corner(word: &str, vert_count: usize, uv_count: usize, n: usize) -> Result<(usize, Option<usize>), String> {
+    let mut fields = word.split('/');
+    let v = resolve_index(word, fields.next().unwrap_or(""), vert_count, "vertices", n)?;
+    let uv = match fields.next() {
+        Some(vt) if !vt.is_empty() => Some(resolve_index(word, vt, uv_count, "uvs", n)?),
+        _ => None,
+    };
+    Ok((v, uv))
+}
+
+pub fn parse_obj(src: &str, materials: &HashMap<String, Material>, default_color: u32) -> Result<(Vec<Triangle3d>, Parts), String> {
+    let mut verts: Vec<V3> = vec![];
+    let mut uvs: Vec<V2> = vec![];
+    let mut triangles = vec![];
+    let mut parts = vec![];
+
+    let mut material = Material::matte(default_color);
+    let mut part_name = "default".to_string();
+    let mut part_start = 0;
+
+    for (n, line) in src.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut words = line.split_whitespace();
+
+        match words.next() {
+            Some("v") => {
+                let xyz = parse_floats(words.take(3), n)?;
+                if xyz.len() < 3 {
+                    return Err(format!("line {}: vertex needs three coordinates", n + 1));
+                }
+                verts.push(V3 {x: xyz[0], y: xyz[1], z: xyz[2]});
+            }
+            Some("vt") => {
+                // a third w coordinate is for 3d textures, which we don't have
+                let uv = parse_floats(words.take(2), n)?;
+                uvs.push(V2::new(uv.first().copied().unwrap_or(0.0), uv.get(1).copied().unwrap_or(0.0)));
+            }
+            Some("f") => {
+                let idx = words.map(|w| resolve_corner(w, verts.len(), uvs.len(), n)).collect::<Result<Vec<_>, _>>()?;
+                if idx.len() < 3 {
+                    return Err(format!("line {}: face needs at least three corners", n + 1));
+                }
+                let uv = |corner: (usize, Option<usize>)| corner.1.map_or(V2::new(0.0, 0.0), |i| uvs[i]);
+                // fan out from the first corner, fine for quads and convex ngons
+                // which is what modelling tools write out
+                for i in 1..idx.len() - 1 {
+                    let mut tri = get_triangle_from_vecs(verts[idx[0].0], verts[idx[i].0], verts[idx[i + 1].0], 0);
+                    (tri.uv0, tri.uv1, tri.uv2) = (uv(idx[0]), uv(idx[i]), uv(idx[i + 1]));
+                    tri.set_material(material.clone());
+                    triangles.push(tri);
+                }
+            }
+            Some("g") | Some("o") => {
+                // going back to a group that was used before starts a new part
+                if triangles.len() > part_start {
+                    add_part(&mut parts, &part_name, part_start, triangles.len());
+                }
+                part_name = words.collect::<Vec<_>>().join(" ");
+                if part_name.is_empty() {
+                    part_name = "default".to_string();
+                }
+                part_start = triangles.len();
+            }
+            Some("usemtl") => {
+                let name = words.next().ok_or(format!("line {}: usemtl without a name", n + 1))?;
+                // an unknown material isn't worth failing the whole model over
+                material = materials.get(name).cloned().unwrap_or(Material::matte(default_color));
+            }
+            _ => {}
+        }
+    }
+
+    if triangles.len() > part_start {
+        add_part(&mut parts, &part_name, part_start, triangles.len());
+    }
+    Ok((triangles, parts))
+}
+
+// writes `path`, and with `materials` its materials to the same name with
+// .mtl, `parts` become groups (triangles outside every part end up in "default")
+pub fn save_obj(path: &Path, triangles: &[Triangle3d], parts: &[(String, usize, usize)], materials: bool) -> Result<(), String> {
+    let mtl_path = path.with_extension("mtl");
+    let mtllib = match materials {
+        true => Some(mtl_path.file_name().and_then(|n| n.to_str()).ok_or(format!("{}: bad file name", path.display()))?),
+        false => None,
+    };
+    let (obj, mtl) = write_obj(triangles, parts, mtllib);
+    fs::write(path, obj).map_err(|e| format!("{}: {}", path.display(), e))?;
+    match mtl {
+        Some(mtl) => fs::write(&mtl_path, mtl).map_err(|e| format!("{}: {}", mtl_path.display(), e)),
+        None => Ok(()),
+    }
+}
+
+// the obj and mtl text, vertices, uvs and normals shared between faces are
+// only written once
+// no mtllib means just the shapes, no usemtl lines and no mtl
+// textures aren't written, the mtl has no file to point map_Kd at
+pub fn write_obj(triangles: &[Triangle3d], parts: &[(String, usize, usize)], mtllib: Option<&str>) -> (String, Option<String>) {
+    let key = |v: V3| (v.x.to_bits(), v.y.to_bits(), v.z.to_bits());
+    let (mut v_lines, mut vt_lines, mut vn_lines) = (String::new(), String::new(), String::new());
+    let (mut obj, mut mtl) = (String::new(), String::new());
+
+    // numbers the first time something is seen, writes it out then too
+    let mut verts = HashMap::new();
+    let mut uvs = HashMap::new();
+    let mut normals = HashMap::new();
+    let index = |map: &mut HashMap<_, usize>, v: V3, kind: &str, out: &mut String| {
+        let next = map.len() + 1;
+        *map.entry(key(v)).or_insert_with(|| {
+            let _ = writeln!(out, "{} {} {} {}", kind, v.x, v.y, v.z);
+            next
+        })
+    };
+
+    // materials are named after their color, with a number on if two differ in something else
+    let mut materials: Vec<(Material, String)> = vec![];
+    let mut current_material = None;
+    let mut current_group = None;
+    for (i, tri) in triangles.iter().enumerate() {
+        let group = parts.iter().find(|(_, start, end)| (*start..*end).contains(&i)).map_or("default", |p| p.0.as_str());
+        if current_group != Some(group) {
+            let _ = writeln!(obj, "g {}", group);
+            current_group = Some(group);
+        }
+
+        if mtllib.is_some() {
+            let name = match materials.iter().find(|(m, _)| same_material(m, &tri.material)) {
+                Some((_, name)) => name.clone(),
+                None => {
+                    let base = format!("color_{:06X}", v3_to_color(tri.material.diffuse));
+                    let taken = materials.iter().filter(|(_, n)| n.starts_with(&base)).count();
+                    let name = if taken == 0 {base} else {format!("{}_{}", base, taken + 1)};
+                    write_mtl(&mut mtl, &name, &tri.material);
+                    materials.push((tri.material.clone(), name.clone()));
+                    name
+                }
+            };
+            if current_material.as_ref() != Some(&name) {
+                let _ = writeln!(obj, "usemtl {}", name);
+                current_material = Some(name);
+            }
+        }
+
+        let mut corners = vec![];
+        for (v, uv, n) in [(tri.v0, tri.uv0, tri.n0), (tri.v1, tri.uv1, tri.n1), (tri.v2, tri.uv2, tri.n2)] {
+            let v = index(&mut verts, v, "v", &mut v_lines);
+            let next = uvs.len() + 1;
+            let vt = *uvs.entry((uv.x.to_bits(), uv.y.to_bits())).or_insert_with(|| {
+                let _ = writeln!(vt_lines, "vt {} {}", uv.x, uv.y);
+                next
+            });
+            let n = index(&mut normals, n, "vn", &mut vn_lines);
+            corners.push(format!("{}/{}/{}", v, vt, n));
+        }
+        let _ = writeln!(obj, "f {}", corners.join(" "));
+    }
+    match mtllib {
+        Some(mtllib) => (format!("mtllib {}\n{}{}{}{}", mtllib, v_lines, vt_lines, vn_lines, obj), Some(mtl)),
+        None => (format!("{}{}{}{}", v_lines, vt_lines, vn_lines, obj), None),
+    }
+}
+
+// whether two triangles can share an mtl entry, a texture is the same if
+// it's the same image: comparing the pixels of every mip level for every
+// triangle adds up fast
+fn same_material(a: &Material, b: &Material) -> bool {
+    let texture = match (&a.texture, &b.texture) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (a, b) => a.is_none() && b.is_none(),
+    };
+    texture && a.diffuse == b.diffuse && a.specular == b.specular && a.shininess == b.shininess
+        && a.emissive == b.emissive && a.sampler == b.sampler
+}
+
+fn write_mtl(out: &mut String, name: &str, m: &Material) {
+    let _ = writeln!(out, "newmtl {}", name);
+    for (key, c) in [("Kd", m.diffuse), ("Ks", m.specular), ("Ke", m.emissive)] {
+        let _ = writeln!(out, "{} {} {} {}", key, c.x, c.y, c.z);
+    }
+    let _ = writeln!(out, "Ns {}\n", m.shininess);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quads_and_groups() {
+        let mtl = parse_mtl("newmtl skin\nKd 1.0 0.5 0.0\nKs 1 1 1\nNs 64\n", Path::new(".")).unwrap();
+        let src = "
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            v 0 0 1
+            vt 0.5 1
+            vt 1 0 0
+            o first
+            f 1 2 3 4
+            g second
+            usemtl skin
+            f 1/1/1 2/2/2 5//5
+            f -1 -2 -3 -4 -5
+        ";
+        let (tris, parts) = parse_obj(src, &mtl, 0xFFFFFF).unwrap();
+        assert_eq!(tris.len(), 2 + 1 + 3);
+        assert_eq!(parts, vec![("first".to_string(), 0, 2), ("second".to_string(), 2, 6)]);
+        assert_eq!(tris[0].color, 0xFFFFFF);
+        assert_eq!(tris[2].color, 0xFF8000);
+        assert_eq!(tris[2].material.specular, V3::new(1.0, 1.0, 1.0));
+        assert_eq!(tris[2].material.shininess, 64.0);
+        assert_eq!(tris[3].v0.z, 1.0); // -1 is the last vertex
+        assert_eq!((tris[2].uv0, tris[2].uv1, tris[2].uv2), (V2::new(0.5, 1.0), V2::new(1.0, 0.0), V2::new(0.0, 0.0)));
+    }
+
+    #[test]
+    fn unreadable_textures_are_skipped() {
+        let mtl = parse_mtl("newmtl skin\nKd 1 0 0\nmap_Kd -clamp on skin.jpg\n", Path::new(".")).unwrap();
+        assert_eq!(mtl["skin"].texture, None);
+        assert_eq!(mtl["skin"].diffuse, V3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn repeated_groups_get_numbered() {
+        let src = "v 0 0 0\nv 1 0 0\nv 0 1 0\ng a\nf 1 2 3\ng b\nf 1 2 3\ng a\nf 1 2 3\n";
+        let (_, parts) = parse_obj(src, &HashMap::new(), 0).unwrap();
+        let names: Vec<&str> = parts.iter().map(|p| p.0.as_str()).collect();
+        assert_eq!(names, ["a", "b", "a_2"]);
+    }
+
+    #[test]
+    fn bad_index_is_an_error() {
+        let err = parse_obj("v 0 0 0\nv 1 0 0\nf 1 2 3\n", &HashMap::new(), 0).unwrap_err();
+        assert!(err.contains("line 3"), "{}", err);
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let mut tris = vec![
+            get_triangle_from_vecs(V3::new(0.0, 0.0, 0.0), V3::new(0.0, 1.0, 0.0), V3::new(1.0, 0.0, 0.0), 0),
+            get_triangle_from_vecs(V3::new(1.0, 0.0, 0.0), V3::new(0.0, 1.0, 0.0), V3::new(1.0, 1.0, 0.5), 0),
+            get_triangle_from_vecs(V3::new(5.0, 0.0, 0.0), V3::new(5.0, 1.0, 0.0), V3::new(6.0, 0.0, 0.0), 0),
+        ];
+        tris[0].set_material(Material::plastic(0xFF0000));
+        tris[1].set_material(Material::matte(0xFF0000));
+        tris[2].set_material(Material::plastic(0x00FF00));
+        (tris[1].uv0, tris[1].uv1, tris[1].uv2) = (V2::new(1.0, 0.0), V2::new(0.0, 1.0), V2::new(1.0, 1.0));
+        let parts = vec![("palm".to_string(), 0, 2), ("thumb".to_string(), 2, 3)];
+
+        let (obj, mtl) = write_obj(&tris, &parts, Some("hand.mtl"));
+        let mtl = mtl.unwrap();
+        assert_eq!(obj.lines().filter(|l| l.starts_with("v ")).count(), 7);
+        assert_eq!(obj.lines().filter(|l| l.starts_with("vt ")).count(), 4);
+        assert!(mtl.contains("newmtl color_FF0000_2"), "{}", mtl);
+
+        let (back, back_parts) = parse_obj(&obj, &parse_mtl(&mtl, Path::new(".")).unwrap(), 0).unwrap();
+        assert_eq!(back_parts, parts);
+        for (a, b) in back.iter().zip(&tris) {
+            assert_eq!((a.v0, a.v1, a.v2), (b.v0, b.v1, b.v2));
+            assert_eq!((a.uv0, a.uv1, a.uv2), (b.uv0, b.uv1, b.uv2));
+            assert_eq!((a.color, a.material.diffuse, a.material.shininess), (b.color, b.material.diffuse, b.material.shininess));
+            // the mtl reader rounds colors to bytes
+            assert!((a.material.specular - b.material.specular).length() < 0.01);
+        }
+    }
+
+    #[test]
+    fn export_without_materials() {
+        let mut tris = vec![
+            get_triangle_from_vecs(V3::new(0.0, 0.0, 0.0), V3::new(0.0, 1.0, 0.0), V3::new(1.0, 0.0, 0.0), 0),
+            get_triangle_from_vecs(V3::new(1.0, 0.0, 0.0), V3::new(0.0, 1.0, 0.0), V3::new(1.0, 1.0, 0.5), 0),
+        ];
+        tris[1].set_material(Material::plastic(0x00FF00));
+        let (obj, mtl) = write_obj(&tris, &[("palm".to_string(), 0, 2)], None);
+        assert_eq!(mtl, None);
+        assert!(!obj.contains("mtllib") && !obj.contains("usemtl"), "{}", obj);
+        let (back, _) = parse_obj(&obj, &HashMap::new(), 0).unwrap();
+        assert_eq!(back.len(), 2);
+    }
+
+    #[test]
+    fn textures_are_the_same_by_pointer() {
+        let checker = Arc::new(Texture::checker(8, 2, 0xFFFFFF, 0x000000));
+        let copy = Arc::new(Texture::checker(8, 2, 0xFFFFFF, 0x000000));
+        let mut tris: Vec<Triangle3d> = (0..3).map(|_| {
+            get_triangle_from_vecs(V3::new(0.0, 0.0, 0.0), V3::new(0.0, 1.0, 0.0), V3::new(1.0, 0.0, 0.0), 0)
+        }).collect();
+        for (tri, texture) in tris.iter_mut().zip([&checker, &checker, &copy]) {
+            let mut material = Material::matte(0xFF0000);
+            material.texture = Some(texture.clone());
+            tri.set_material(material);
+        }
+        let (obj, mtl) = write_obj(&tris, &[], Some("hand.mtl"));
+        let mtl = mtl.unwrap();
+        // the copy has the same pixels but is its own image
+        assert_eq!(mtl.matches("newmtl").count(), 2, "{}", mtl);
+        assert_eq!(obj.matches("usemtl").count(), 2, "{}", obj);
+    }
+}
@@ -0,0 +1,159 @@
+// writing the framebuffer out to image files so we can render without a window
+// the buffer is the same 0x00RRGGBB Vec<u32> minifb eats
+// no crate-level stuff in here on purpose, spinning_cube pulls this file in too
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ImageFormat {Ppm, Png}
+
+impl ImageFormat {
+    pub fn parse(s: &str) -> Option<ImageFormat> {
+        match s.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Ppm => "ppm",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+// what the headless mode was asked to do from the command line
+pub struct Headless {
+    pub frames: usize,
+    pub format: ImageFormat,
+    pub out_dir: PathBuf,
+}
+
+pub const USAGE: &str = "options:
+    --headless          render without a window and write frames to disk
+    --frames <n>        number of frames to render in headless mode (default 60)
+    --format <ppm|png>  image format for headless frames (default ppm)
+    --out <dir>         where the frames go (default frames)";
+
+// tiny hand rolled parser, not worth a dependency for four flags
+// returns None when we should just open the window like normal
+pub fn parse_args(args: &[String]) -> Result<Option<Headless>, String> {
+    let mut headless = false;
+    let mut frames = 60;
+    let mut format = ImageFormat::Ppm;
+    let mut out_dir = PathBuf::from("frames");
+
+    let mut i = 0;
+    while i < args.len() {
+        let value = |i: usize| args.get(i + 1).ok_or(format!("{} needs a value", args[i]));
+        match args[i].as_str() {
+            "--headless" => headless = true,
+            "--frames" => {
+                frames = value(i)?.parse().map_err(|_| format!("bad frame count: {}", args[i + 1]))?;
+                i += 1;
+            }
+            "--format" => {
+                format = ImageFormat::parse(value(i)?).ok_or(format!("unknown format: {}", args[i + 1]))?;
+                i += 1;
+            }
+            "--out" => {
+                out_dir = PathBuf::from(value(i)?);
+                i += 1;
+            }
+            other => return Err(format!("unknown argument: {}", other)),
+        }
+        i += 1;
+    }
+
+    if !headless {
+        return Ok(None);
+    }
+    Ok(Some(Headless {frames, format, out_dir}))
+}
+
+fn to_rgb(buf: &[u32]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(buf.len() * 3);
+    for px in buf {
+        out.push((px >> 16 & 0xFF) as u8);
+        out.push((px >> 8 & 0xFF) as u8);
+        out.push((px & 0xFF) as u8);
+    }
+    out
+}
+
+// binary P6, about as simple as an image format gets
+pub fn write_ppm(path: &Path, buf: &[u32], width: usize, height: usize) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    write!(w, "P6\n{} {}\n255\n", width, height)?;
+    w.write_all(&to_rgb(buf))?;
+    w.flush()
+}
+
+pub fn write_png(path: &Path, buf: &[u32], width: usize, height: usize) -> io::Result<()> {
+    let w = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(w, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&to_rgb(buf)).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+pub fn write_image(path: &Path, format: ImageFormat, buf: &[u32], width: usize, height: usize) -> io::Result<()> {
+    match format {
+        ImageFormat::Ppm => write_ppm(path, buf, width, height),
+        ImageFormat::Png => write_png(path, buf, width, height),
+    }
+}
+
+// numbered frames in the output dir, frame_0000.ppm, frame_0001.ppm, ...
+pub fn write_frame(settings: &Headless, frame: usize, buf: &[u32], width: usize, height: usize) -> io::Result<PathBuf> {
+    fs::create_dir_all(&settings.out_dir)?;
+    let path = settings.out_dir.join(format!("frame_{:04}.{}", frame, settings.format.extension()));
+    write_image(&path, settings.format, buf, width, height)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn flags() {
+        assert!(parse_args(&[]).unwrap().is_none());
+        // the other flags alone don't switch the window off
+        assert!(parse_args(&args("--frames 3 --format png")).unwrap().is_none());
+
+        let h = parse_args(&args("--headless")).unwrap().unwrap();
+        assert_eq!((h.frames, h.format, h.out_dir), (60, ImageFormat::Ppm, PathBuf::from("frames")));
+        let h = parse_args(&args("--frames 3 --out shots --headless --format PNG")).unwrap().unwrap();
+        assert_eq!((h.frames, h.format, h.out_dir), (3, ImageFormat::Png, PathBuf::from("shots")));
+
+        assert_eq!(parse_args(&args("--headless --frames")).err().unwrap(), "--frames needs a value");
+        assert_eq!(parse_args(&args("--headless --out")).err().unwrap(), "--out needs a value");
+        assert_eq!(parse_args(&args("--headless --frames -2")).err().unwrap(), "bad frame count: -2");
+        assert_eq!(parse_args(&args("--headless --format gif")).err().unwrap(), "unknown format: gif");
+        assert_eq!(parse_args(&args("--headless --fast")).err().unwrap(), "unknown argument: --fast");
+    }
+
+    #[test]
+    fn ppm_bytes() {
+        let dir = std::env::temp_dir().join(format!("output-test-{}", std::process::id()));
+        let settings = Headless {frames: 1, format: ImageFormat::Ppm, out_dir: dir.clone()};
+        let path = write_frame(&settings, 7, &[0xFF0000, 0x00FF00, 0x0000FF, 0x123456], 2, 2).unwrap();
+        assert_eq!(path, dir.join("frame_0007.ppm"));
+        let mut expected = b"P6\n2 2\n255\n".to_vec();
+        expected.extend([0xFF, 0, 0, 0, 0xFF, 0, 0, 0, 0xFF, 0x12, 0x34, 0x56]);
+        assert_eq!(fs::read(&path).unwrap(), expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,328 @@
+// .ply files, what 3D scanners hand out
+// a text header says which elements follow, how many of each and what
+// properties they have, then the data comes as text or as raw numbers in
+// either byte order:
+//   ply
+//   format ascii 1.0          (or binary_little_endian / binary_big_endian)
+//   element vertex 8
+//   property float x          (y, z, nx, ny, nz, red, green, blue, ...)
+//   element face 6
+//   property list uchar int vertex_indices
+//   end_header
+// we use vertex positions, normals and colors and the face lists, any other
+// element or property is read past and dropped
+// a triangle only has one color so it gets the average of its corners, scans
+// are dense enough that nobody can tell
+// corners go round the same way as in obj files
+
+use std::fs;
+use std::path::Path;
+
+use crate::light::{v3_to_color, Material};
+use crate::obj::Parts;
+use crate::{get_triangle_from_vecs, smooth_normals, Triangle3d, V3};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Format {Ascii, LittleEndian, BigEndian}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Scalar {I8, U8, I16, U16, I32, U32, F32, F64}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Kind {
+    Scalar(Scalar),
+    List {count: Scalar, item: Scalar},
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<(String, Kind)>,
+}
+
+impl Scalar {
+    fn parse(s: &str) -> Option<Scalar> {
+        match s {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+}
+
+// the whole file is one part named after it, ply has no names inside
+pub fn load_ply(path: &Path, default_color: u32) -> Result<(Vec<Triangle3d>, Parts), String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let triangles = parse_ply(&bytes, default_color).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let name = path.file_stem().map_or("model".to_string(), |s| s.to_string_lossy().into_owned());
+    let len = triangles.len();
+    Ok((triangles, vec![(name, 0, len)]))
+}
+
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), String> {
+    let mut lines = header.lines().enumerate();
+    if lines.next().map(|(_, l)| l.trim()) != Some("ply") {
+        return Err("not a ply file, it doesn't start with \"ply\"".to_string());
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for (n, line) in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let err = |msg: &str| format!("header line {}: {}", n + 1, msg);
+        match words.as_slice() {
+            [] | ["comment", ..] | ["obj_info", ..] => {}
+            ["format", f, _version] => {
+                format = Some(match *f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::LittleEndian,
+                    "binary_big_endian" => Format::BigEndian,
+                    _ => return Err(err(&format!("unknown format {}", f))),
+                });
+            }
+            ["element", name, count] => {
+                let count = count.parse().map_err(|_| err(&format!("bad element count {:?}", count)))?;
+                elements.push(Element {name: name.to_string(), count, properties: vec![]});
+            }
+            ["property", "list", count, item, name] => {
+                let (Some(count), Some(item)) = (Scalar::parse(count), Scalar::parse(item)) else {
+                    return Err(err(&format!("unknown type in list {}", name)));
+                };
+                let element = elements.last_mut().ok_or_else(|| err("property before any element"))?;
+                element.properties.push((name.to_string(), Kind::List {count, item}));
+            }
+            ["property", kind, name] => {
+                let kind = Scalar::parse(kind).ok_or_else(|| err(&format!("unknown type {}", kind)))?;
+                let element = elements.last_mut().ok_or_else(|| err("property before any element"))?;
+                element.properties.push((name.to_string(), Kind::Scalar(kind)));
+            }
+            _ => return Err(err(&format!("don't understand {:?}", line.trim()))),
+        }
+    }
+    // nothing to read means nothing to stop a count of billions looping on
+    if let Some(e) = elements.iter().find(|e| e.count > 0 && e.properties.is_empty()) {
+        return Err(format!("element {} has {} entries but no properties", e.name, e.count));
+    }
+    Ok((format.ok_or("the header has no format line")?, elements))
+}
+
+// list lengths and vertex indices, which have to be whole and not negative,
+// `as usize` would quietly turn -1 into 0 and 1.5 into 1
+fn whole(v: f64) -> Option<usize> {
+    (v >= 0.0 && v.fract() == 0.0 && v <= usize::MAX as f64).then_some(v as usize)
+}
+
+// hands out the numbers in the body one at a time, whatever the format
+struct Reader<'a> {
+    format: Format,
+    words: std::str::SplitAsciiWhitespace<'a>,
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(format: Format, body: &'a [u8]) -> Result<Reader<'a>, String> {
+        let text = if format == Format::Ascii {
+            std::str::from_utf8(body).map_err(|_| "ascii ply with bytes that aren't text in it")?
+        } else {
+            ""
+        };
+        Ok(Reader {format, words: text.split_ascii_whitespace(), bytes: body, pos: 0})
+    }
+
+    fn read(&mut self, kind: Scalar) -> Option<f64> {
+        if self.format == Format::Ascii {
+            return self.words.next()?.parse().ok();
+        }
+        let raw = self.bytes.get(self.pos..self.pos + kind.size())?;
+        self.pos += kind.size();
+        let mut buf = [0u8; 8];
+        buf[..raw.len()].copy_from_slice(raw);
+        if self.format == Format::BigEndian {
+            buf[..raw.len()].reverse();
+        }
+        Some(match kind {
+            Scalar::I8 => buf[0] as i8 as f64,
+            Scalar::U8 => buf[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes(buf[..4].try_into().unwrap()) as f64,
+            Scalar::U32 => u32::from_le_bytes(buf[..4].try_into().unwrap()) as f64,
+            Scalar::F32 => f32::from_le_bytes(buf[..4].try_into().unwrap()) as f64,
+            Scalar::F64 => f64::from_le_bytes(buf),
+        })
+    }
+}
+
+struct Vertex {
+    position: V3,
+    normal: Option<V3>,
+    color: Option<V3>,
+}
+
+pub fn parse_ply(bytes: &[u8], default_color: u32) -> Result<Vec<Triangle3d>, String> {
+    // the header is text even when the rest isn't
+    let end = bytes.windows(10).position(|w| w == b"end_header").ok_or("no end_header, not a ply file or cut short")?;
+    let body_start = bytes[end..].iter().position(|&b| b == b'\n').map_or(bytes.len(), |i| end + i + 1);
+    let header = std::str::from_utf8(&bytes[..end]).map_err(|_| "the header isn't text")?;
+    let (format, elements) = parse_header(header)?;
+    let mut reader = Reader::new(format, &bytes[body_start..])?;
+
+    let mut vertices: Vec<Vertex> = vec![];
+    let mut faces: Vec<Vec<usize>> = vec![];
+    for element in &elements {
+        for i in 0..element.count {
+            let short = || format!("{} {} of {} is cut short or has a bad number in it", element.name, i, element.count);
+            let mut values: Vec<(&str, f64)> = vec![];
+            let mut list = vec![];
+            for (name, kind) in &element.properties {
+                match *kind {
+                    Kind::Scalar(s) => {
+                        let v = reader.read(s).ok_or_else(short)?;
+                        // color channels in whole number types go 0..255, float ones 0..1
+                        let v = if ["red", "green", "blue"].contains(&name.as_str()) && s != Scalar::F32 && s != Scalar::F64 {v / 255.0} else {v};
+                        values.push((name, v));
+                    }
+                    Kind::List {count, item} => {
+                        let len = reader.read(count).and_then(whole).ok_or_else(short)?;
+                        let items = (0..len).map(|_| reader.read(item)).collect::<Option<Vec<_>>>().ok_or_else(short)?;
+                        if name == "vertex_indices" || name == "vertex_index" {
+                            list = items;
+                        }
+                    }
+                }
+            }
+            let get = |n: &str| values.iter().find(|(name, _)| *name == n).map(|(_, v)| *v);
+            let v3 = |x, y, z| Some(V3::new(get(x)?, get(y)?, get(z)?));
+            match element.name.as_str() {
+                "vertex" => vertices.push(Vertex {
+                    position: v3("x", "y", "z").ok_or("vertices need x, y and z")?,
+                    normal: v3("nx", "ny", "nz"),
+                    color: v3("red", "green", "blue"),
+                }),
+                "face" => faces.push(list.iter().map(|&i| whole(i)).collect::<Option<_>>()
+                    .ok_or_else(|| format!("face {} has a vertex index that isn't a whole number", i))?),
+                _ => {}
+            }
+        }
+    }
+
+    let mut out = vec![];
+    let has_normals = vertices.iter().all(|v| v.normal.is_some());
+    for (f, face) in faces.iter().enumerate() {
+        if face.len() < 3 {
+            return Err(format!("face {} has {} corners, it needs at least three", f, face.len()));
+        }
+        if let Some(&bad) = face.iter().find(|&&i| i >= vertices.len()) {
+            return Err(format!("face {} uses vertex {} but there are only {}", f, bad, vertices.len()));
+        }
+        // fan out from the first corner like the obj loader
+        for i in 1..face.len() - 1 {
+            let corners = [&vertices[face[0]], &vertices[face[i]], &vertices[face[i + 1]]];
+            let color = match corners.map(|c| c.color) {
+                [Some(a), Some(b), Some(c)] => v3_to_color((a + b + c) / 3.0),
+                _ => default_color,
+            };
+            let mut tri = get_triangle_from_vecs(corners[0].position, corners[1].position, corners[2].position, color);
+            tri.set_material(Material::matte(color));
+            if has_normals {
+                (tri.n0, tri.n1, tri.n2) = (corners[0].normal.unwrap().normalize(), corners[1].normal.unwrap().normalize(), corners[2].normal.unwrap().normalize());
+            }
+            out.push(tri);
+        }
+    }
+    // scans without normals still want to look smooth
+    if !has_normals {
+        smooth_normals(&mut out);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::color_to_v3;
+
+    #[test]
+    fn ascii_with_colors() {
+        let src = "ply
+format ascii 1.0
+comment a red quad with one blue corner
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 255 0 0
+1 1 0 255 0 0
+0 1 0 0 0 255
+4 0 1 2 3
+";
+        let tris = parse_ply(src.as_bytes(), 0xFFFFFF).unwrap();
+        assert_eq!(tris.len(), 2);
+        assert_eq!(tris[0].color, 0xFF0000);
+        assert_eq!(tris[1].color, 0xAA0055);
+        assert_eq!(tris[1].v2, V3::new(0.0, 1.0, 0.0));
+        assert_eq!(tris[0].material.diffuse, color_to_v3(0xFF0000));
+    }
+
+    #[test]
+    fn binary_big_endian_with_normals() {
+        let mut bytes = b"ply\nformat binary_big_endian 1.0\nelement vertex 3\nproperty double x\nproperty double y\nproperty double z\nproperty float nx\nproperty float ny\nproperty float nz\nelement face 1\nproperty list uchar uint vertex_index\nend_header\n".to_vec();
+        for (p, n) in [([0.0, 0.0, 0.0], [0.0f32, 0.0, 2.0]), ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]), ([1.0, 0.0, 0.0], [0.0, 0.0, 1.0])] {
+            p.iter().for_each(|c: &f64| bytes.extend(c.to_be_bytes()));
+            n.iter().for_each(|c| bytes.extend(c.to_be_bytes()));
+        }
+        bytes.push(3);
+        [0u32, 1, 2].iter().for_each(|i| bytes.extend(i.to_be_bytes()));
+
+        let tris = parse_ply(&bytes, 0x123456).unwrap();
+        assert_eq!(tris.len(), 1);
+        assert_eq!((tris[0].v1, tris[0].color), (V3::new(0.0, 1.0, 0.0), 0x123456));
+        assert_eq!(tris[0].n0, V3::new(0.0, 0.0, 1.0));
+
+        bytes.truncate(bytes.len() - 3);
+        assert!(parse_ply(&bytes, 0).unwrap_err().contains("face 0 of 1 is cut short"));
+    }
+
+    #[test]
+    fn bad_files() {
+        let head = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n";
+        assert!(parse_ply(format!("{}0 0 0\n3 0 1 2\n", head).as_bytes(), 0).unwrap_err().contains("uses vertex 1 but there are only 1"));
+        assert!(parse_ply(b"ply\nformat ascii 1.0\n", 0).unwrap_err().contains("no end_header"));
+        assert!(parse_ply(b"ply\nformat ascii 1.0\nproperty float x\nend_header\n", 0).unwrap_err().contains("property before any element"));
+
+        let three = head.replace("vertex 1", "vertex 3");
+        let body = "0 0 0\n1 0 0\n0 1 0\n";
+        assert!(parse_ply(format!("{}{}3 0 1 2\n", three, body).as_bytes(), 0).is_ok());
+        assert!(parse_ply(format!("{}{}3 0 1 -1\n", three, body).as_bytes(), 0).unwrap_err().contains("isn't a whole number"));
+        let float_indices = three.replace("uchar int", "uchar float");
+        assert!(parse_ply(format!("{}{}3 0 1 1.5\n", float_indices, body).as_bytes(), 0).unwrap_err().contains("isn't a whole number"));
+        let signed_count = three.replace("uchar int", "char int");
+        assert!(parse_ply(format!("{}{}-1 0 1 2\n", signed_count, body).as_bytes(), 0).unwrap_err().contains("face 0 of 1 is cut short or has a bad number"));
+        let empty = "ply\nformat ascii 1.0\nelement nothing 4000000000\nend_header\n";
+        assert!(parse_ply(empty.as_bytes(), 0).unwrap_err().contains("element nothing has 4000000000 entries but no properties"));
+    }
+}
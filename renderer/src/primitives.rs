@@ -0,0 +1,204 @@
+// parametric shapes, so models can be built out of round things instead of
+// typing every vertex out
+// everything comes out centered on the origin with y up, move it into place
+// with transform_triangles
+// the vertex normals are the real ones from the shape (not averaged faces) and
+// every triangle goes round clockwise seen from outside, like get_box_triangles
+// the uvs wrap the texture around once: u around the y axis and v from the
+// bottom of the profile to the top, or across x and z for the plane
+//
+// all the round ones are lathes: a 2D profile of (radius, height) points spun
+// around the y axis, `segments` says how many slices around
+
+use std::f64::consts::{PI, TAU};
+
+use crate::math::{V2, V3};
+use crate::{get_triangle_from_vecs, Triangle3d};
+
+// one point of a lathe profile: how far out, how high, and the normal there
+// in the same (out, up) terms
+#[derive(Clone, Copy, Debug)]
+struct ProfilePoint {r: f64, y: f64, nr: f64, ny: f64}
+
+fn point(r: f64, y: f64, nr: f64, ny: f64) -> ProfilePoint {
+    ProfilePoint {r, y, nr, ny}
+}
+
+// a corner: position, normal and texture coordinate
+type Corner = (V3, V3, V2);
+
+// a triangle with its own corner normals, None when it has no area
+// (the slivers at a pole or where the profile doubles back on itself)
+fn tri(a: Corner, b: Corner, c: Corner, color: u32) -> Option<Triangle3d> {
+    if (b.0 - a.0).cross(c.0 - a.0).length() < 1e-12 {
+        return None;
+    }
+    let mut t = get_triangle_from_vecs(a.0, b.0, c.0, color);
+    (t.n0, t.n1, t.n2) = (a.1, b.1, c.1);
+    (t.uv0, t.uv1, t.uv2) = (a.2, b.2, c.2);
+    Some(t)
+}
+
+// two triangles for the quad a-b-c-d, the a-c diagonal is shared
+fn quad(out: &mut Vec<Triangle3d>, a: Corner, b: Corner, c: Corner, d: Corner, color: u32) {
+    out.extend(tri(a, d, c, color));
+    out.extend(tri(a, c, b, color));
+}
+
+// spins the profile around y, the profile has to run so the outside is on
+// its left going (out, up), i.e. top to bottom for something like a sphere
+fn lathe(profile: &[ProfilePoint], segments: usize, color: u32) -> Vec<Triangle3d> {
+    let segments = segments.max(3);
+    let last = (profile.len() - 1).max(1) as f64;
+    let at = |i: usize, s: usize| {
+        let p = profile[i];
+        let around = s as f64 / segments as f64;
+        let (sin, cos) = (TAU * around).sin_cos();
+        (V3::new(p.r * sin, p.y, p.r * cos), V3::new(p.nr * sin, p.ny, p.nr * cos).normalize(), V2::new(around, 1.0 - i as f64 / last))
+    };
+
+    let mut out = vec![];
+    for i in 0..profile.len().saturating_sub(1) {
+        for s in 0..segments {
+            let (a, b) = (at(i, s), at(i, s + 1));
+            let (d, c) = (at(i + 1, s), at(i + 1, s + 1));
+            quad(&mut out, a, b, c, d, color);
+        }
+    }
+    out
+}
+
+// quarter circle of `rings` steps from straight up (or down) to the equator,
+// for spheres and capsule ends
+fn arc(radius: f64, y: f64, rings: usize, top: bool) -> Vec<ProfilePoint> {
+    (0..=rings.max(1))
+        .map(|i| {
+            let t = i as f64 / rings.max(1) as f64;
+            let a = if top {t * PI / 2.0} else {PI / 2.0 + t * PI / 2.0};
+            let (nr, ny) = (a.sin(), a.cos());
+            point(radius * nr, y + radius * ny, nr, ny)
+        })
+        .collect()
+}
+
+// `rings` is how many slices from pole to pole
+pub fn sphere(radius: f64, segments: usize, rings: usize, color: u32) -> Vec<Triangle3d> {
+    let half = rings.max(2).div_ceil(2);
+    let mut profile = arc(radius, 0.0, half, true);
+    profile.extend(arc(radius, 0.0, half, false).into_iter().skip(1));
+    lathe(&profile, segments, color)
+}
+
+// open tube unless `caps`, the caps get their own flat normals so the rim is a hard edge
+pub fn cylinder(radius: f64, height: f64, segments: usize, caps: bool, color: u32) -> Vec<Triangle3d> {
+    let h = height / 2.0;
+    let mut profile = vec![];
+    if caps {
+        profile.extend([point(0.0, h, 0.0, 1.0), point(radius, h, 0.0, 1.0)]);
+    }
+    profile.extend([point(radius, h, 1.0, 0.0), point(radius, -h, 1.0, 0.0)]);
+    if caps {
+        profile.extend([point(radius, -h, 0.0, -1.0), point(0.0, -h, 0.0, -1.0)]);
+    }
+    lathe(&profile, segments, color)
+}
+
+// point up, base down and closed
+pub fn cone(radius: f64, height: f64, segments: usize, color: u32) -> Vec<Triangle3d> {
+    let h = height / 2.0;
+    // the side normal leans up by however steep the side is
+    let len = (height * height + radius * radius).sqrt();
+    let (nr, ny) = (height / len, radius / len);
+    lathe(&[
+        point(0.0, h, nr, ny),
+        point(radius, -h, nr, ny),
+        point(radius, -h, 0.0, -1.0),
+        point(0.0, -h, 0.0, -1.0),
+    ], segments, color)
+}
+
+// a ring lying flat, `major` from the center to the middle of the tube and
+// `minor` the radius of the tube itself, `sides` slices around the tube
+pub fn torus(major: f64, minor: f64, segments: usize, sides: usize, color: u32) -> Vec<Triangle3d> {
+    let sides = sides.max(3);
+    // once around the tube, going the way that keeps the outside on the left
+    let profile: Vec<_> = (0..=sides)
+        .map(|i| {
+            let a = TAU * (1.0 - i as f64 / sides as f64);
+            let (nr, ny) = (a.cos(), a.sin());
+            point(major + minor * nr, minor * ny, nr, ny)
+        })
+        .collect();
+    lathe(&profile, segments, color)
+}
+
+// flat on the ground facing up, `div_x` by `div_z` quads
+pub fn plane(width: f64, depth: f64, div_x: usize, div_z: usize, color: u32) -> Vec<Triangle3d> {
+    let (div_x, div_z) = (div_x.max(1), div_z.max(1));
+    let up = V3::new(0.0, 1.0, 0.0);
+    let at = |i: usize, j: usize| {
+        let (u, v) = (i as f64 / div_x as f64, j as f64 / div_z as f64);
+        (V3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth), up, V2::new(u, v))
+    };
+
+    let mut out = vec![];
+    for i in 0..div_x {
+        for j in 0..div_z {
+            quad(&mut out, at(i, j), at(i + 1, j), at(i + 1, j + 1), at(i, j + 1), color);
+        }
+    }
+    out
+}
+
+// a cylinder with half spheres on the ends, good for fingers
+// `height` is the straight part only, the whole thing is height + 2 radius tall
+// `rings` is the number of slices in each end
+pub fn capsule(radius: f64, height: f64, segments: usize, rings: usize, color: u32) -> Vec<Triangle3d> {
+    let h = height / 2.0;
+    let mut profile = arc(radius, h, rings, true);
+    profile.extend(arc(radius, -h, rings, false));
+    lathe(&profile, segments, color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every face has to point away from `inside(center of the face)`, and the
+    // corner normals have to agree with the face
+    fn check_outwards(tris: &[Triangle3d], inside: impl Fn(V3) -> V3) {
+        assert!(!tris.is_empty());
+        for t in tris {
+            let n = t.face_normal();
+            assert!(n.dot(t.center() - inside(t.center())) > 0.0, "{:?} points inwards", t);
+            for corner in [t.n0, t.n1, t.n2] {
+                assert!((corner.length() - 1.0).abs() < 1e-9);
+                assert!(corner.dot(n) > 0.0, "{:?} has a normal against its face", t);
+            }
+        }
+    }
+
+    #[test]
+    fn everything_winds_outwards() {
+        let origin = |_| V3::ZERO;
+        check_outwards(&sphere(10.0, 12, 8, 0), origin);
+        check_outwards(&cylinder(10.0, 30.0, 12, true, 0), origin);
+        check_outwards(&cone(10.0, 30.0, 12, 0), origin);
+        check_outwards(&capsule(5.0, 20.0, 12, 4, 0), origin);
+        check_outwards(&plane(10.0, 10.0, 3, 2, 0), |p| p - V3::new(0.0, 1.0, 0.0));
+        // the middle of the tube right under the face
+        check_outwards(&torus(20.0, 5.0, 16, 8, 0), |p| V3::new(p.x, 0.0, p.z).normalize() * 20.0);
+    }
+
+    #[test]
+    fn triangle_counts() {
+        // the poles lose a triangle per segment
+        assert_eq!(sphere(1.0, 12, 8, 0).len(), 12 * 8 * 2 - 2 * 12);
+        assert_eq!(cylinder(1.0, 1.0, 12, false, 0).len(), 12 * 2);
+        assert_eq!(cylinder(1.0, 1.0, 12, true, 0).len(), 12 * 4);
+        assert_eq!(cone(1.0, 1.0, 12, 0).len(), 12 * 2);
+        assert_eq!(torus(2.0, 1.0, 12, 6, 0).len(), 12 * 6 * 2);
+        assert_eq!(plane(1.0, 1.0, 3, 2, 0).len(), 3 * 2 * 2);
+        assert_eq!(capsule(1.0, 1.0, 12, 4, 0).len(), 12 * (4 + 1 + 4) * 2 - 2 * 12);
+    }
+}
@@ -0,0 +1,187 @@
+// quaternions, for when euler angles get in the way
+// three angles applied x then y then z lock up when y hits 90 degrees (two
+// of the axes line up and one degree of freedom is just gone), and blending
+// the angles one by one takes weird detours between poses
+// a unit quaternion is one rotation about one axis, and slerp moves between
+// two of them along the shortest arc at a constant speed
+//
+// same order as Mat4::rotate: from_euler(v) rotates x first, then y, then z
+
+use std::ops::Mul;
+
+use crate::math::{Mat4, V3};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quat {pub w: f64, pub x: f64, pub y: f64, pub z: f64}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat {w: 1.0, x: 0.0, y: 0.0, z: 0.0};
+
+    // angle in radians around axis, the axis doesn't have to be normalized
+    pub fn from_axis_angle(axis: V3, angle: f64) -> Quat {
+        let axis = axis.normalize();
+        if axis == V3::ZERO {
+            return Quat::IDENTITY;
+        }
+        let (s, c) = (angle * 0.5).sin_cos();
+        Quat {w: c, x: axis.x * s, y: axis.y * s, z: axis.z * s}
+    }
+
+    pub fn from_euler(angles: V3) -> Quat {
+        let qx = Quat::from_axis_angle(V3::new(1.0, 0.0, 0.0), angles.x);
+        let qy = Quat::from_axis_angle(V3::new(0.0, 1.0, 0.0), angles.y);
+        let qz = Quat::from_axis_angle(V3::new(0.0, 0.0, 1.0), angles.z);
+        qz * qy * qx
+    }
+
+    // back to x, y, z angles, y comes out in -90..90 degrees
+    // right at +-90 x and z turn the same way so z is set to 0 and x gets it all
+    pub fn to_euler(self) -> V3 {
+        let m = self.to_mat4().m;
+        let sy = (-m[2][0]).clamp(-1.0, 1.0);
+        if sy.abs() > 1.0 - 1e-9 {
+            return V3::new((-m[1][2]).atan2(m[1][1]), sy.asin(), 0.0);
+        }
+        V3::new(m[2][1].atan2(m[2][2]), sy.asin(), m[1][0].atan2(m[0][0]))
+    }
+
+    pub fn dot(self, o: Quat) -> f64 {
+        self.w * o.w + self.x * o.x + self.y * o.y + self.z * o.z
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Quat {
+        let len = self.length();
+        if len == 0.0 {
+            return Quat::IDENTITY;
+        }
+        Quat {w: self.w / len, x: self.x / len, y: self.y / len, z: self.z / len}
+    }
+
+    pub fn rotate(self, v: V3) -> V3 {
+        // q v q*, expanded so there's no quaternion product per vertex
+        let u = V3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        v + t * self.w + u.cross(t)
+    }
+
+    pub fn to_mat4(self) -> Mat4 {
+        let Quat {w, x, y, z} = self;
+        Mat4 {m: [
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y), 0.0],
+            [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x), 0.0],
+            [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]}
+    }
+
+    // t = 0 gives self, t = 1 gives o, always the short way around
+    pub fn slerp(self, o: Quat, t: f64) -> Quat {
+        // q and -q are the same rotation, pick whichever is closer
+        let mut d = self.dot(o);
+        let o = if d < 0.0 {
+            d = -d;
+            Quat {w: -o.w, x: -o.x, y: -o.y, z: -o.z}
+        } else {
+            o
+        };
+
+        // nearly the same rotation, sin(theta) is tiny so just lerp
+        let (a, b) = if d > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = d.acos();
+            let s = theta.sin();
+            (((1.0 - t) * theta).sin() / s, (t * theta).sin() / s)
+        };
+        Quat {
+            w: self.w * a + o.w * b,
+            x: self.x * a + o.x * b,
+            y: self.y * a + o.y * b,
+            z: self.z * a + o.z * b,
+        }.normalize()
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Quat {
+        Quat::IDENTITY
+    }
+}
+
+// a * b rotates by b first, then a, same as Mat4
+impl Mul for Quat {
+    type Output = Quat;
+    fn mul(self, o: Quat) -> Quat {
+        Quat {
+            w: self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+            x: self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            y: self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            z: self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    fn close(a: V3, b: V3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    #[test]
+    fn matches_the_matrices() {
+        let angles = V3::new(0.3, -1.1, 2.0);
+        let q = Quat::from_euler(angles);
+        let m = Mat4::rotate(angles);
+        for v in [V3::new(1.0, 0.0, 0.0), V3::new(3.0, -2.0, 5.0)] {
+            assert!(close(q.rotate(v), m.transform_point(v)));
+            assert!(close(q.to_mat4().transform_point(v), m.transform_point(v)));
+        }
+        let axis = Quat::from_axis_angle(V3::new(0.0, 0.0, 2.0), FRAC_PI_2);
+        assert!(close(axis.rotate(V3::new(1.0, 0.0, 0.0)), V3::new(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn turns_triangles_like_the_matrices() {
+        use crate::{get_box_triangles, rotate_triangles, rotate_triangles_quat, WHITE};
+        let angles = V3::new(0.3, -1.1, 2.0);
+        let cube = get_box_triangles(V3::new(5.0, 0.0, -2.0), V3::new(1.0, 2.0, 3.0), [WHITE; 6]);
+        let by_quat = rotate_triangles_quat(cube.clone(), Quat::from_euler(angles));
+        let by_matrix = rotate_triangles(cube, angles.x, angles.y, angles.z);
+        for (a, b) in by_quat.iter().zip(&by_matrix) {
+            assert!(close(a.v0, b.v0) && close(a.v1, b.v1) && close(a.v2, b.v2));
+            assert!(close(a.n0, b.n0) && close(a.n1, b.n1) && close(a.n2, b.n2));
+        }
+    }
+
+    #[test]
+    fn euler_round_trip() {
+        for angles in [V3::new(0.3, -1.1, 2.0), V3::new(-2.5, 0.4, -0.7), V3::ZERO] {
+            assert!(close(Quat::from_euler(angles).to_euler(), angles));
+        }
+        // gimbal lock, the angles can't come back but the rotation has to
+        let locked = Quat::from_euler(V3::new(0.5, FRAC_PI_2, 0.2));
+        let back = Quat::from_euler(locked.to_euler());
+        let v = V3::new(3.0, -2.0, 5.0);
+        assert!(close(locked.rotate(v), back.rotate(v)));
+    }
+
+    #[test]
+    fn slerp_takes_the_short_way() {
+        let a = Quat::IDENTITY;
+        let b = Quat::from_axis_angle(V3::new(0.0, 1.0, 0.0), FRAC_PI_2);
+        assert!(close(a.slerp(b, 0.5).to_euler(), V3::new(0.0, FRAC_PI_2 / 2.0, 0.0)));
+        assert!(close(a.slerp(b, 0.0).to_euler(), V3::ZERO));
+        assert!(close(a.slerp(b, 1.0).to_euler(), V3::new(0.0, FRAC_PI_2, 0.0)));
+
+        // 350 degrees is 10 the other way, halfway is -5 not 175
+        let c = Quat::from_axis_angle(V3::new(0.0, 0.0, 1.0), 2.0 * PI - 0.1745);
+        assert!(close(a.slerp(c, 0.5).to_euler(), V3::new(0.0, 0.0, -0.1745 / 2.0)));
+    }
+}
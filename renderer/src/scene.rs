@@ -0,0 +1,137 @@
+// a little scene graph: every node has a parent and a local transform, and
+// a node's world transform is its own transform followed by all of its
+// parents', so bending a knuckle drags the rest of the finger along with it
+//
+// all pivots are in the mesh's rest pose coordinates, which keeps things
+// simple: a child's pivot is wherever the joint is in the unposed model
+
+use crate::math::{Mat4, V3};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    pub rotation: V3,    // euler angles, applied x then y then z like Mat4::rotate
+    pub pivot: V3,       // the point the rotation happens around
+    pub translation: V3, // applied after the rotation
+}
+
+impl Transform {
+    pub fn at_pivot(pivot: V3) -> Transform {
+        Transform {rotation: V3::ZERO, pivot, translation: V3::ZERO}
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::translate(self.translation) * Mat4::rotate_around(self.rotation, self.pivot)
+    }
+
+    pub fn apply(&self, v: V3) -> V3 {
+        self.matrix().transform_point(v)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub local: Transform,
+}
+
+// nodes are stored parents first, add() won't take a parent it hasn't seen yet
+#[derive(Clone, Debug, Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+}
+
+impl SceneGraph {
+    pub fn new() -> SceneGraph {
+        SceneGraph {nodes: vec![]}
+    }
+
+    pub fn add(&mut self, name: &str, parent: Option<&str>, pivot: V3) -> usize {
+        let parent = parent.map(|p| self.index(p).unwrap_or_else(|| panic!("{} needs its parent {} added first", name, p)));
+        self.nodes.push(Node {name: name.to_string(), parent, local: Transform::at_pivot(pivot)});
+        self.nodes.len() - 1
+    }
+
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|n| n.name == name)
+    }
+
+    pub fn node_mut(&mut self, name: &str) -> &mut Node {
+        let i = self.index(name).unwrap_or_else(|| panic!("the scene has no node named {}", name));
+        &mut self.nodes[i]
+    }
+
+    pub fn set_rotation(&mut self, name: &str, ax: f64, ay: f64, az: f64) {
+        self.node_mut(name).local.rotation = V3::new(ax, ay, az);
+    }
+
+    // every node's local matrix with all of its parents' in front of it,
+    // parents come first in the list so one pass is enough
+    pub fn world_matrices(&self) -> Vec<Mat4> {
+        let mut world: Vec<Mat4> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let local = node.local.matrix();
+            world.push(match node.parent {
+                Some(p) => world[p] * local,
+                None => local,
+            });
+        }
+        world
+    }
+
+    // local first, then every parent up to the root
+    pub fn world_point(&self, node: usize, v: V3) -> V3 {
+        let mut p = v;
+        let mut cur = Some(node);
+        while let Some(i) = cur {
+            p = self.nodes[i].local.apply(p);
+            cur = self.nodes[i].parent;
+        }
+        p
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn close(a: V3, b: V3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    // a palm turned a quarter round z about the wrist at the origin, and a
+    // finger on it curled a quarter round x about its knuckle at (0, 10, 0)
+    fn hand() -> SceneGraph {
+        let mut scene = SceneGraph::new();
+        scene.add("palm", None, V3::ZERO);
+        scene.add("finger", Some("palm"), V3::new(0.0, 10.0, 0.0));
+        scene.set_rotation("palm", 0.0, 0.0, FRAC_PI_2);
+        scene.set_rotation("finger", FRAC_PI_2, 0.0, 0.0);
+        scene
+    }
+
+    #[test]
+    fn children_turn_about_their_pivot_then_follow_the_parent() {
+        let scene = hand();
+        let finger = scene.index("finger").unwrap();
+        // the tip curls to (0, 10, 10) about the knuckle, then the palm swings it round
+        let tip = V3::new(0.0, 20.0, 0.0);
+        assert!(close(scene.world_point(finger, tip), V3::new(-10.0, 0.0, 10.0)));
+        // the knuckle itself only moves with the palm
+        assert!(close(scene.world_point(finger, V3::new(0.0, 10.0, 0.0)), V3::new(-10.0, 0.0, 0.0)));
+
+        // the matrices say the same thing as walking up the parents
+        let world = scene.world_matrices();
+        assert!(close(world[finger].transform_point(tip), scene.world_point(finger, tip)));
+        assert!(close(world[0].transform_point(tip), V3::new(-20.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn translation_comes_after_the_rotation() {
+        let mut scene = hand();
+        scene.node_mut("palm").local.translation = V3::new(5.0, 0.0, 0.0);
+        let finger = scene.index("finger").unwrap();
+        assert!(close(scene.world_point(finger, V3::new(0.0, 20.0, 0.0)), V3::new(-5.0, 0.0, 10.0)));
+    }
+}
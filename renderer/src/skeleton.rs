@@ -0,0 +1,179 @@
+// a 3D skeleton for posing meshes by joint angles
+// joints sit in rest pose coordinates and every mesh part is bound to exactly
+// one of them (rigid skinning, each cuboid just follows its bone)
+// under the hood the joints turn into a scene graph, the skeleton only adds
+// limits, bindings and named poses on top
+
+use crate::mesh::Mesh;
+use crate::scene::SceneGraph;
+use crate::math::V3;
+
+#[derive(Clone, Debug)]
+pub struct Joint {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub position: V3, // where the joint is in the rest pose
+    pub min: V3,      // angle limits per axis, radians
+    pub max: V3,
+    pub angles: V3,   // current angles, always inside the limits
+}
+
+// a pose is just joint angles by name, joints it doesn't mention go back to rest
+pub type Pose = [(&'static str, V3)];
+
+#[derive(Clone, Debug, Default)]
+pub struct Skeleton {
+    joints: Vec<Joint>,
+    bindings: Vec<(String, usize)>, // mesh part -> joint
+}
+
+fn clamp_v3(v: V3, min: V3, max: V3) -> V3 {
+    V3 {x: v.x.clamp(min.x, max.x), y: v.y.clamp(min.y, max.y), z: v.z.clamp(min.z, max.z)}
+}
+
+impl Skeleton {
+    pub fn new() -> Skeleton {
+        Skeleton {joints: vec![], bindings: vec![]}
+    }
+
+    pub fn add_joint(&mut self, name: &str, parent: Option<&str>, position: V3, min: V3, max: V3) -> usize {
+        let parent = parent.map(|p| self.index(p).unwrap_or_else(|| panic!("{} needs its parent {} added first", name, p)));
+        let rest = clamp_v3(V3::ZERO, min, max);
+        self.joints.push(Joint {name: name.to_string(), parent, position, min, max, angles: rest});
+        self.joints.len() - 1
+    }
+
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|j| j.name == name)
+    }
+
+    pub fn joints(&self) -> &[Joint] {
+        &self.joints
+    }
+
+    // the mesh part `part` follows `joint` from now on
+    pub fn bind(&mut self, part: &str, joint: &str) {
+        let j = self.index(joint).unwrap_or_else(|| panic!("the skeleton has no joint named {}", joint));
+        self.bindings.retain(|b| b.0 != part);
+        self.bindings.push((part.to_string(), j));
+    }
+
+    // clamps to the joint's limits, hands back what was actually set
+    pub fn set_angles(&mut self, joint: &str, angles: V3) -> V3 {
+        let i = self.index(joint).unwrap_or_else(|| panic!("the skeleton has no joint named {}", joint));
+        let j = &mut self.joints[i];
+        j.angles = clamp_v3(angles, j.min, j.max);
+        j.angles
+    }
+
+    pub fn reset(&mut self) {
+        for j in &mut self.joints {
+            j.angles = clamp_v3(V3::ZERO, j.min, j.max);
+        }
+    }
+
+    // joints the pose names that this skeleton doesn't have are skipped, the
+    // hand poses still have to work on a hand file somebody has been editing
+    pub fn set_pose(&mut self, pose: &Pose) {
+        self.reset();
+        for (name, angles) in pose {
+            if self.index(name).is_some() {
+                self.set_angles(name, *angles);
+            }
+        }
+    }
+
+    // the joints as scene graph nodes, each one rotating around its own position
+    pub fn to_scene(&self) -> SceneGraph {
+        let mut scene = SceneGraph::new();
+        for j in &self.joints {
+            let parent = j.parent.map(|p| self.joints[p].name.as_str());
+            scene.add(&j.name, parent, j.position);
+            scene.set_rotation(&j.name, j.angles.x, j.angles.y, j.angles.z);
+        }
+        scene
+    }
+
+    // where every joint ended up after posing, same order as joints()
+    pub fn world_positions(&self) -> Vec<V3> {
+        let scene = self.to_scene();
+        self.joints.iter().enumerate().map(|(i, j)| scene.world_point(i, j.position)).collect()
+    }
+
+    // moves every bound part of the rest mesh along with its joint
+    pub fn skin(&self, rest: &Mesh) -> Mesh {
+        let world = self.to_scene().world_matrices();
+        let mut posed = rest.clone();
+        for (part, joint) in &self.bindings {
+            posed.transform_part_by(part, &world[*joint]);
+        }
+        posed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{get_box_triangles, WHITE};
+    use std::f64::consts::FRAC_PI_2;
+
+    fn close(a: V3, b: V3) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    // a wrist at the origin and one finger whose knuckle only bends forward round x
+    fn finger() -> Skeleton {
+        let mut skeleton = Skeleton::new();
+        skeleton.add_joint("wrist", None, V3::ZERO, V3::new(-1.0, -1.0, -1.0), V3::new(1.0, 1.0, 1.0));
+        skeleton.add_joint("knuckle", Some("wrist"), V3::new(0.0, 10.0, 0.0), V3::ZERO, V3::new(FRAC_PI_2, 0.0, 0.0));
+        skeleton
+    }
+
+    #[test]
+    fn angles_stay_inside_the_limits() {
+        let mut skeleton = finger();
+        assert!(close(skeleton.set_angles("knuckle", V3::new(3.0, 0.5, -0.5)), V3::new(FRAC_PI_2, 0.0, 0.0)));
+        assert!(close(skeleton.set_angles("knuckle", V3::new(-1.0, 0.0, 0.0)), V3::ZERO));
+        assert!(close(skeleton.set_angles("wrist", V3::new(0.5, -2.0, 2.0)), V3::new(0.5, -1.0, 1.0)));
+        assert!(close(skeleton.joints()[0].angles, V3::new(0.5, -1.0, 1.0)));
+
+        // a rest pose the limits don't allow starts at the nearest allowed angle
+        skeleton.add_joint("tip", Some("knuckle"), V3::new(0.0, 15.0, 0.0), V3::new(0.2, 0.0, 0.0), V3::new(1.0, 0.0, 0.0));
+        assert!(close(skeleton.joints()[2].angles, V3::new(0.2, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn poses_skip_joints_that_are_not_there() {
+        let mut skeleton = finger();
+        skeleton.set_angles("wrist", V3::new(0.5, 0.0, 0.0));
+        skeleton.set_pose(&[("thumb", V3::new(1.0, 0.0, 0.0)), ("knuckle", V3::new(1.0, 0.0, 0.0))]);
+        // the wrist isn't in the pose so it went back to rest
+        assert!(close(skeleton.joints()[0].angles, V3::ZERO));
+        assert!(close(skeleton.joints()[1].angles, V3::new(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn skinning_moves_only_bound_parts() {
+        let mut triangles = get_box_triangles(V3::new(0.0, 5.0, 0.0), V3::new(5.0, 5.0, 1.0), [WHITE; 6]);
+        triangles.extend(get_box_triangles(V3::new(0.0, 15.0, 0.0), V3::new(1.0, 5.0, 1.0), [WHITE; 6]));
+        triangles.extend(get_box_triangles(V3::new(20.0, 0.0, 0.0), V3::new(1.0, 1.0, 1.0), [WHITE; 6]));
+        let rest = Mesh::new(triangles, vec![("palm".into(), 0, 12), ("finger".into(), 12, 24), ("loose".into(), 24, 36)]);
+
+        let mut skeleton = finger();
+        skeleton.bind("palm", "wrist");
+        skeleton.bind("finger", "knuckle");
+        skeleton.set_angles("knuckle", V3::new(FRAC_PI_2, 0.0, 0.0));
+        let posed = skeleton.skin(&rest);
+
+        // the palm's joint hasn't moved and the loose part has no joint at all
+        for part in ["palm", "loose"] {
+            for (a, b) in posed.part(part).unwrap().iter().zip(rest.part(part).unwrap()) {
+                assert!(close(a.v0, b.v0) && close(a.v1, b.v1) && close(a.v2, b.v2) && close(a.n0, b.n0));
+            }
+        }
+        // the finger folds forward round its knuckle, its far end goes from y = 20 to z = 10
+        let (min, max) = posed.part_bounds("finger").unwrap();
+        assert!(close(min, V3::new(-1.0, 9.0, 0.0)) && close(max, V3::new(1.0, 11.0, 10.0)), "{:?} {:?}", min, max);
+        assert!(close(skeleton.world_positions()[1], V3::new(0.0, 10.0, 0.0)));
+    }
+}
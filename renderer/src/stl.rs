@@ -0,0 +1,230 @@
+// stl files, what 3D printers and slicers eat: nothing but triangles, each
+// with a facet normal, which is all a Triangle3d is anyway
+// two flavours:
+//   ascii  - "solid name", then facet normal / outer loop / 3 vertex lines /
+//            endloop / endfacet for every triangle, then "endsolid"
+//   binary - 80 byte header, a u32 triangle count, then 50 bytes per triangle:
+//            normal and three corners as little endian f32s, and a u16 nobody
+//            agrees on the meaning of (we write 0 and ignore it)
+// stl has no units, colors or parts, so loading takes a color for everything
+// the stored normals aren't trusted on the way in, lots of exporters write
+// zeros, the triangles get theirs from the corners like everywhere else
+// corners go the same way round as in obj files, so no winding fix is needed
+
+use std::fs;
+use std::path::Path;
+
+use crate::{get_triangle_from_vecs, Triangle3d, V3};
+
+const HEADER_LEN: usize = 80;
+const FACET_LEN: usize = 50;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StlFormat {Ascii, Binary}
+
+pub fn load_stl(path: &Path, color: u32) -> Result<Vec<Triangle3d>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    parse_stl(&bytes, color).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+pub fn save_stl(path: &Path, triangles: &[Triangle3d], format: StlFormat) -> Result<(), String> {
+    let bytes = match format {
+        StlFormat::Ascii => {
+            let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("model");
+            stl_ascii(name, triangles).into_bytes()
+        }
+        StlFormat::Binary => stl_binary(triangles),
+    };
+    fs::write(path, bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// binary files are allowed to start with "solid" too (plenty of exporters
+// put it in the header), so a file whose size matches its triangle count
+// exactly is binary no matter what the first bytes say
+pub fn parse_stl(bytes: &[u8], color: u32) -> Result<Vec<Triangle3d>, String> {
+    if let Some(count) = binary_count(bytes).filter(|&count| binary_len(count) == bytes.len()) {
+        return parse_binary(bytes, count, color);
+    }
+    let text = std::str::from_utf8(bytes).ok().filter(|s| s.trim_start().starts_with("solid"));
+    match (text, binary_count(bytes)) {
+        (Some(text), _) => parse_ascii(text, color),
+        (None, Some(count)) => Err(format!(
+            "binary stl says {} triangles, that's {} bytes but the file is {}",
+            count, binary_len(count), bytes.len())),
+        (None, None) => Err("not an stl file: too short for binary and doesn't start with \"solid\"".to_string()),
+    }
+}
+
+fn binary_count(bytes: &[u8]) -> Option<usize> {
+    let count = bytes.get(HEADER_LEN..HEADER_LEN + 4)?;
+    Some(u32::from_le_bytes(count.try_into().unwrap()) as usize)
+}
+
+fn binary_len(count: usize) -> usize {
+    HEADER_LEN + 4 + count * FACET_LEN
+}
+
+fn parse_binary(bytes: &[u8], count: usize, color: u32) -> Result<Vec<Triangle3d>, String> {
+    let mut out = Vec::with_capacity(count);
+    for (i, facet) in bytes[HEADER_LEN + 4..].chunks_exact(FACET_LEN).enumerate() {
+        let f = |k: usize| f32::from_le_bytes(facet[k * 4..k * 4 + 4].try_into().unwrap()) as f64;
+        // skip the normal, floats 0..3
+        let corner = |c: usize| V3::new(f(3 + c * 3), f(4 + c * 3), f(5 + c * 3));
+        let (v0, v1, v2) = (corner(0), corner(1), corner(2));
+        if [v0, v1, v2].iter().any(|v| !(v.x.is_finite() && v.y.is_finite() && v.z.is_finite())) {
+            return Err(format!("triangle {}: corner that isn't a number", i));
+        }
+        out.push(get_triangle_from_vecs(v0, v1, v2, color));
+    }
+    Ok(out)
+}
+
+// where the ascii parser is, every keyword is only allowed in one of these
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum At {Outside, Solid, Facet, Loop}
+
+fn parse_ascii(src: &str, color: u32) -> Result<Vec<Triangle3d>, String> {
+    let mut out = vec![];
+    let mut corners: Vec<V3> = vec![];
+    let mut at = At::Outside;
+
+    for (n, line) in src.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        let Some(key) = words.next() else { continue };
+        let err = |msg: String| Err(format!("line {}: {}", n + 1, msg));
+        match (key, at) {
+            // the name is optional, and files with more than one solid in them exist
+            ("solid", At::Outside) => at = At::Solid,
+            ("endsolid", At::Solid) => at = At::Outside,
+            ("facet", At::Solid) => {
+                if words.next() != Some("normal") {
+                    return err("expected \"facet normal\"".to_string());
+                }
+                // checked so a broken line gets reported, but not used
+                xyz(words, n)?;
+                at = At::Facet;
+            }
+            ("outer", At::Facet) => {
+                if words.next() != Some("loop") {
+                    return err("expected \"outer loop\"".to_string());
+                }
+                corners.clear();
+                at = At::Loop;
+            }
+            ("vertex", At::Loop) => corners.push(xyz(words, n)?),
+            ("endloop", At::Loop) => {
+                if corners.len() != 3 {
+                    return err(format!("facet has {} vertices, stl facets are triangles", corners.len()));
+                }
+                at = At::Facet;
+            }
+            ("endfacet", At::Facet) => {
+                if corners.len() != 3 {
+                    return err("facet without an outer loop".to_string());
+                }
+                out.push(get_triangle_from_vecs(corners[0], corners[1], corners[2], color));
+                corners.clear();
+                at = At::Solid;
+            }
+            (key, At::Outside) => return err(format!("{:?} outside of a solid", key)),
+            (key, _) => return err(format!("unexpected {:?}", key)),
+        }
+    }
+
+    match at {
+        At::Outside => Ok(out),
+        At::Solid => Err("file ends without endsolid".to_string()),
+        At::Facet | At::Loop => Err("file ends in the middle of a facet".to_string()),
+    }
+}
+
+fn xyz<'a>(words: impl Iterator<Item = &'a str>, n: usize) -> Result<V3, String> {
+    let values = words
+        .map(|w| w.parse::<f64>().ok().filter(|v| v.is_finite()).ok_or(format!("line {}: bad number {:?}", n + 1, w)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if values.len() != 3 {
+        return Err(format!("line {}: expected three numbers, got {}", n + 1, values.len()));
+    }
+    Ok(V3::new(values[0], values[1], values[2]))
+}
+
+// the facet normal printers want, straight from the corners
+fn facet_normal(tri: &Triangle3d) -> V3 {
+    tri.face_normal().normalize()
+}
+
+pub fn stl_ascii(name: &str, triangles: &[Triangle3d]) -> String {
+    let mut out = format!("solid {}\n", name);
+    for tri in triangles {
+        let n = facet_normal(tri);
+        out += &format!("  facet normal {} {} {}\n    outer loop\n", n.x, n.y, n.z);
+        for v in [tri.v0, tri.v1, tri.v2] {
+            out += &format!("      vertex {} {} {}\n", v.x, v.y, v.z);
+        }
+        out += "    endloop\n  endfacet\n";
+    }
+    out + &format!("endsolid {}\n", name)
+}
+
+pub fn stl_binary(triangles: &[Triangle3d]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(binary_len(triangles.len()));
+    // the header can say anything as long as it doesn't look like ascii
+    out.extend_from_slice(b"binary stl from the renderer");
+    out.resize(HEADER_LEN, 0);
+    out.extend((triangles.len() as u32).to_le_bytes());
+    for tri in triangles {
+        for v in [facet_normal(tri), tri.v0, tri.v1, tri.v2] {
+            for c in [v.x, v.y, v.z] {
+                out.extend((c as f32).to_le_bytes());
+            }
+        }
+        out.extend(0u16.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tetrahedron() -> Vec<Triangle3d> {
+        let (a, b, c, d) = (V3::new(0.0, 0.0, 0.0), V3::new(1.0, 0.0, 0.0), V3::new(0.0, 1.0, 0.0), V3::new(0.0, 0.0, 1.0));
+        [(a, c, b), (a, b, d), (a, d, c), (b, c, d)].iter().map(|&(p, q, r)| get_triangle_from_vecs(p, q, r, 0)).collect()
+    }
+
+    #[test]
+    fn round_trips() {
+        let tris = tetrahedron();
+        let ascii = stl_ascii("tet", &tris);
+        assert!(ascii.contains("facet normal 0 0 -1"), "{}", ascii);
+        for parsed in [parse_stl(ascii.as_bytes(), 0x00FF00).unwrap(), parse_stl(&stl_binary(&tris), 0x00FF00).unwrap()] {
+            assert_eq!(parsed.len(), 4);
+            for (p, t) in parsed.iter().zip(&tris) {
+                assert_eq!((p.v0, p.v1, p.v2), (t.v0, t.v1, t.v2));
+                assert_eq!(p.n0, t.n0);
+                assert_eq!(p.color, 0x00FF00);
+            }
+        }
+    }
+
+    #[test]
+    fn binary_that_starts_with_solid() {
+        let mut bytes = stl_binary(&tetrahedron());
+        bytes[..5].copy_from_slice(b"solid");
+        assert_eq!(parse_stl(&bytes, 0).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn broken_files_say_whats_wrong() {
+        let err = |src: &str| parse_stl(src.as_bytes(), 0).unwrap_err();
+        assert!(err("hello").contains("not an stl file"));
+        assert!(err("solid x\n").contains("without endsolid"));
+        assert!(err("solid x\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nendloop\n").contains("line 6: facet has 2 vertices"));
+        assert!(err("solid x\nfacet normal 0 0 one\n").contains("line 2: bad number \"one\""));
+        assert!(err("solid x\nvertex 0 0 0\n").contains("line 2: unexpected \"vertex\""));
+
+        let mut bytes = stl_binary(&tetrahedron());
+        bytes.truncate(bytes.len() - 10);
+        assert!(parse_stl(&bytes, 0).unwrap_err().contains("says 4 triangles, that's 284 bytes but the file is 274"));
+    }
+}
@@ -0,0 +1,401 @@
+// images to stick on triangles, each corner says which spot of the image it
+// gets with a (u, v) and the rasterizer blends those across the triangle
+// u goes left to right and v bottom to top, (0, 0) is the bottom left corner
+// like obj and opengl have it, so the first row of pixels is v = 1
+// what happens outside 0..1 is the wrap mode:
+//   Repeat - the image tiles forever
+//   Clamp  - the edge pixels get smeared out
+// and how the image is read is the filter:
+//   Nearest   - the one texel the point lands in, blocky up close
+//   Bilinear  - the four texels around it blended by distance, smooth but soft
+//   Trilinear - bilinear from the two mip levels closest to the pixel's size,
+//               blended, so far away and edge on faces don't shimmer
+// the mip levels are made when the texture is, each half the size of the one
+// before down to 1x1, and the rasterizer says how much of the texture one
+// pixel covers (the uv derivatives) to choose between them
+// nearest and bilinear always read the full size image
+// anisotropy goes with trilinear: a face seen edge on covers a long thin
+// strip of the texture per pixel, one lookup sized for the long side blurs
+// everything, so instead up to that many lookups sized for the short side
+// are taken along the strip and averaged
+
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::light::color_to_v3;
+use crate::math::{V2, V3};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Filter {Nearest, Bilinear, Trilinear}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Wrap {Repeat, Clamp}
+
+impl Filter {
+    pub fn parse(s: &str) -> Option<Filter> {
+        match s {
+            "nearest" => Some(Filter::Nearest),
+            "bilinear" => Some(Filter::Bilinear),
+            "trilinear" => Some(Filter::Trilinear),
+            _ => None,
+        }
+    }
+}
+
+impl Wrap {
+    pub fn parse(s: &str) -> Option<Wrap> {
+        match s {
+            "repeat" => Some(Wrap::Repeat),
+            "clamp" => Some(Wrap::Clamp),
+            _ => None,
+        }
+    }
+}
+
+// how a material reads its texture
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Sampler {
+    pub filter: Filter,
+    pub wrap: Wrap,
+    pub anisotropy: usize, // most lookups per pixel, 1 is off
+}
+
+// the most anisotropy that's allowed, like the 16x graphics cards stop at
+pub const MAX_ANISOTROPY: usize = 16;
+
+impl Default for Sampler {
+    fn default() -> Sampler {
+        Sampler {filter: Filter::Trilinear, wrap: Wrap::Repeat, anisotropy: 1}
+    }
+}
+
+// one image of the mip chain, colors are kept as V3s in 0..1 so filtering
+// is just arithmetic
+#[derive(Clone, PartialEq, Debug)]
+struct Level {
+    width: usize,
+    height: usize,
+    pixels: Vec<V3>,
+}
+
+impl Level {
+    // the texel at integer coordinates, x right and y down, wrapped into the image
+    fn texel(&self, x: i64, y: i64, wrap: Wrap) -> V3 {
+        let (w, h) = (self.width as i64, self.height as i64);
+        let (x, y) = match wrap {
+            Wrap::Repeat => (x.rem_euclid(w), y.rem_euclid(h)),
+            Wrap::Clamp => (x.clamp(0, w - 1), y.clamp(0, h - 1)),
+        };
+        self.pixels[(y * w + x) as usize]
+    }
+
+    // uv in texels, flipped so y runs down the image
+    fn to_texels(&self, uv: V2) -> (f64, f64) {
+        (uv.x * self.width as f64, (1.0 - uv.y) * self.height as f64)
+    }
+
+    fn nearest(&self, uv: V2, wrap: Wrap) -> V3 {
+        let (x, y) = self.to_texels(uv);
+        self.texel(x.floor() as i64, y.floor() as i64, wrap)
+    }
+
+    fn bilinear(&self, uv: V2, wrap: Wrap) -> V3 {
+        // texel centers sit on the halves
+        let (x, y) = self.to_texels(uv);
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = self.texel(x0, y0, wrap).lerp(self.texel(x0 + 1, y0, wrap), tx);
+        let bottom = self.texel(x0, y0 + 1, wrap).lerp(self.texel(x0 + 1, y0 + 1, wrap), tx);
+        top.lerp(bottom, ty)
+    }
+
+    // the next level down, half the size (rounded down, at least 1) with
+    // every texel the average of the ones it covers, with odd sizes the
+    // middle ones get shared between neighbours
+    fn halve(&self) -> Level {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let span = |i: usize, from: usize, to: usize| i * from / to..((i + 1) * from).div_ceil(to);
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (xs, ys) = (span(x, self.width, width), span(y, self.height, height));
+                let count = (xs.len() * ys.len()) as f64;
+                let sum = ys.flat_map(|sy| xs.clone().map(move |sx| (sx, sy)))
+                    .fold(V3::ZERO, |sum, (sx, sy)| sum + self.pixels[sy * self.width + sx]);
+                pixels.push(sum / count);
+            }
+        }
+        Level {width, height, pixels}
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    levels: Vec<Level>, // the full image first, 1x1 last
+}
+
+impl Texture {
+    // 0xRRGGBB pixels row by row from the top, like the framebuffer
+    pub fn new(width: usize, height: usize, pixels: &[u32]) -> Texture {
+        assert!(width > 0 && height > 0 && pixels.len() == width * height,
+                "{} pixels for a {}x{} texture", pixels.len(), width, height);
+        let mut levels = vec![Level {width, height, pixels: pixels.iter().map(|&c| color_to_v3(c)).collect()}];
+        while let Some(last) = levels.last().filter(|l| l.width > 1 || l.height > 1) {
+            levels.push(last.halve());
+        }
+        Texture {width, height, levels}
+    }
+
+    #[cfg(test)]
+    pub fn mip_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    // a size x size checkerboard of cells x cells squares, handy for seeing
+    // how a texture sits on a model
+    pub fn checker(size: usize, cells: usize, a: u32, b: u32) -> Texture {
+        let cell = (size / cells.max(1)).max(1);
+        let pixels: Vec<u32> = (0..size * size)
+            .map(|i| if (i % size / cell + i / size / cell).is_multiple_of(2) {a} else {b})
+            .collect();
+        Texture::new(size, size, &pixels)
+    }
+
+    // .ppm or .png, by the extension
+    pub fn load(path: &Path) -> Result<Texture, String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let texture = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("ppm") => parse_ppm(&bytes),
+            Some("png") => parse_png(&bytes),
+            _ => Err("textures have to be .ppm or .png".to_string()),
+        };
+        texture.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // the full size texel at integer coordinates, x right and y down
+    #[cfg(test)]
+    fn texel(&self, x: i64, y: i64, wrap: Wrap) -> V3 {
+        self.levels[0].texel(x, y, wrap)
+    }
+
+    // the color at `uv`, `dx` and `dy` are how far uv moves one pixel to the
+    // right and one pixel down on screen, only trilinear looks at them
+    pub fn sample(&self, uv: V2, dx: V2, dy: V2, sampler: Sampler) -> V3 {
+        match sampler.filter {
+            Filter::Nearest => self.levels[0].nearest(uv, sampler.wrap),
+            Filter::Bilinear => self.levels[0].bilinear(uv, sampler.wrap),
+            Filter::Trilinear => {
+                // the pixel's footprint in full size texels, its long and short sides
+                let size = |d: V2| (d.x * self.width as f64).hypot(d.y * self.height as f64);
+                let (major, minor) = if size(dx) >= size(dy) {(dx, size(dy))} else {(dy, size(dx))};
+                let major_len = size(major);
+                if !major_len.is_finite() {
+                    // right on the horizon, as small as it gets
+                    return self.trilinear(uv, f64::INFINITY, sampler.wrap);
+                }
+
+                // one lookup per how many times longer than wide the footprint
+                // is, each covering a square piece of it
+                let most = sampler.anisotropy.clamp(1, MAX_ANISOTROPY);
+                let taps = if minor > 0.0 {((major_len / minor).ceil() as usize).clamp(1, most)} else {most};
+                let lod = (major_len / taps as f64).log2();
+                if taps == 1 {
+                    return self.trilinear(uv, lod, sampler.wrap);
+                }
+                let sum = (0..taps).fold(V3::ZERO, |sum, i| {
+                    let along = (i as f64 + 0.5) / taps as f64 - 0.5;
+                    sum + self.trilinear(uv + major * along, lod, sampler.wrap)
+                });
+                sum / taps as f64
+            }
+        }
+    }
+
+    // bilinear from the levels either side of `lod` (log2 of texels per
+    // pixel), up close that's just the full image
+    fn trilinear(&self, uv: V2, lod: f64, wrap: Wrap) -> V3 {
+        let last = self.levels.len() - 1;
+        let lod = lod.clamp(0.0, last as f64);
+        let level = lod.floor() as usize;
+        let t = lod - level as f64;
+        let near = self.levels[level].bilinear(uv, wrap);
+        if t == 0.0 || level == last {
+            return near;
+        }
+        near.lerp(self.levels[level + 1].bilinear(uv, wrap), t)
+    }
+}
+
+// P3 (numbers as text) or P6 (raw bytes, two per channel when maxval is
+// over 255), what output.rs writes is P6
+pub fn parse_ppm(bytes: &[u8]) -> Result<Texture, String> {
+    // the header is whitespace separated with # comments to the end of the line
+    let mut pos = 0;
+    let mut word = || {
+        loop {
+            match bytes.get(pos) {
+                Some(b'#') => while bytes.get(pos).is_some_and(|&b| b != b'\n') {pos += 1},
+                Some(b) if b.is_ascii_whitespace() => pos += 1,
+                _ => break,
+            }
+        }
+        let start = pos;
+        while bytes.get(pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+            pos += 1;
+        }
+        std::str::from_utf8(&bytes[start..pos]).unwrap_or("").to_string()
+    };
+
+    let magic = word();
+    if magic != "P3" && magic != "P6" {
+        return Err(format!("not a ppm file, expected P3 or P6 and got {:?}", magic));
+    }
+    let mut number = |what: &str| word().parse::<usize>().map_err(|_| format!("bad {} in the header", what));
+    let (width, height, max) = (number("width")?, number("height")?, number("maxval")?);
+    if width == 0 || height == 0 || max == 0 || max > 65535 {
+        return Err(format!("{}x{} with maxval {} isn't an image", width, height, max));
+    }
+
+    // the sizes come straight from the file, a silly one mustn't wrap around
+    let too_large = || format!("{}x{} image too large", width, height);
+    let count = width.checked_mul(height).and_then(|n| n.checked_mul(3)).ok_or_else(too_large)?;
+    let values: Vec<usize> = if magic == "P3" {
+        let values = (0..count).map(|_| word().parse().ok()).collect::<Option<Vec<usize>>>();
+        values.ok_or("pixel data is cut short or has a bad number")?
+    } else {
+        // exactly one whitespace byte between the header and the data
+        let data = bytes.get(pos + 1..).unwrap_or(&[]);
+        let size = if max > 255 {2} else {1};
+        if data.len() < count.checked_mul(size).ok_or_else(too_large)? {
+            return Err(format!("pixel data is cut short, {} bytes for {} values", data.len(), count));
+        }
+        data.chunks(size).take(count).map(|c| c.iter().fold(0, |v, &b| v << 8 | b as usize)).collect()
+    };
+
+    let scale = |v: usize| (v.min(max) * 255 + max / 2) / max;
+    let pixels: Vec<u32> = values.chunks(3).map(|c| (scale(c[0]) << 16 | scale(c[1]) << 8 | scale(c[2])) as u32).collect();
+    Ok(Texture::new(width, height, &pixels))
+}
+
+// any png, turned into 8 bit rgb, alpha is dropped since nothing here blends
+pub fn parse_png(bytes: &[u8]) -> Result<Texture, String> {
+    let mut decoder = png::Decoder::new(Cursor::new(bytes));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut data = vec![0; reader.output_buffer_size().ok_or("png too big")?];
+    let info = reader.next_frame(&mut data).map_err(|e| e.to_string())?;
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        png::ColorType::Indexed => return Err("palette png that didn't get expanded".to_string()),
+    };
+    let pixels: Vec<u32> = data[..info.buffer_size()]
+        .chunks(channels)
+        .map(|c| {
+            let (r, g, b) = if channels < 3 {(c[0], c[0], c[0])} else {(c[0], c[1], c[2])};
+            (r as u32) << 16 | (g as u32) << 8 | b as u32
+        })
+        .collect();
+    Ok(Texture::new(info.width as usize, info.height as usize, &pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2x2: red green on top, blue white below
+    fn quad() -> Texture {
+        Texture::new(2, 2, &[0xFF0000, 0x00FF00, 0x0000FF, 0xFFFFFF])
+    }
+
+    #[test]
+    fn nearest_and_wrapping() {
+        let t = quad();
+        let nearest = |wrap| Sampler {filter: Filter::Nearest, wrap, anisotropy: 1};
+        assert_eq!(t.sample(V2::new(0.25, 0.75), V2::ZERO, V2::ZERO, nearest(Wrap::Repeat)), V3::new(1.0, 0.0, 0.0));
+        assert_eq!(t.sample(V2::new(0.75, 0.25), V2::ZERO, V2::ZERO, nearest(Wrap::Repeat)), V3::new(1.0, 1.0, 1.0));
+        // one whole image to the right lands on the same texel, clamped stays on the edge
+        assert_eq!(t.sample(V2::new(1.25, 0.75), V2::ZERO, V2::ZERO, nearest(Wrap::Repeat)), V3::new(1.0, 0.0, 0.0));
+        assert_eq!(t.sample(V2::new(1.25, 0.75), V2::ZERO, V2::ZERO, nearest(Wrap::Clamp)), V3::new(0.0, 1.0, 0.0));
+        assert_eq!(t.sample(V2::new(-3.0, -3.0), V2::ZERO, V2::ZERO, nearest(Wrap::Clamp)), V3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn bilinear_blends() {
+        let t = quad();
+        let clamp = Sampler {filter: Filter::Bilinear, wrap: Wrap::Clamp, anisotropy: 1};
+        // on a texel center it's that texel, dead middle is the average of all four
+        assert_eq!(t.sample(V2::new(0.25, 0.75), V2::ZERO, V2::ZERO, clamp), V3::new(1.0, 0.0, 0.0));
+        assert_eq!(t.sample(V2::new(0.5, 0.5), V2::ZERO, V2::ZERO, clamp), V3::new(0.5, 0.5, 0.5));
+        let top = t.sample(V2::new(0.5, 0.75), V2::ZERO, V2::ZERO, clamp);
+        assert!((top.x - 0.5).abs() < 1e-9 && (top.y - 0.5).abs() < 1e-9 && top.z == 0.0);
+    }
+
+    #[test]
+    fn mip_chain() {
+        // 8x8 checker of 1 texel cells, down to 1x1 every level is the gray average
+        let t = Texture::checker(8, 8, 0xFFFFFF, 0x000000);
+        assert_eq!(t.mip_levels(), 4);
+        assert!(t.levels[1..].iter().all(|l| l.pixels.iter().all(|&p| p == V3::new(0.5, 0.5, 0.5))));
+        // odd sizes share the middle texels, 3x1 -> 1x1 is the average of all three
+        let odd = Texture::new(3, 1, &[0xFF0000, 0x00FF00, 0x0000FF]);
+        assert_eq!((odd.levels[1].width, odd.levels[1].height), (1, 1));
+        assert!((odd.levels[1].pixels[0] - V3::new(1.0, 1.0, 1.0) / 3.0).length() < 1e-9);
+    }
+
+    #[test]
+    fn lod_from_derivatives() {
+        let t = Texture::checker(64, 64, 0xFFFFFF, 0x000000);
+        let trilinear = Sampler::default();
+        let texel = 1.0 / 64.0;
+        // a pixel a texel wide sees the checker, one 64 texels wide sees the average
+        let close = t.sample(V2::new(0.5 * texel, 0.5 * texel), V2::new(texel, 0.0), V2::new(0.0, texel), trilinear);
+        assert!(close == V3::new(1.0, 1.0, 1.0) || close == V3::ZERO);
+        let far = t.sample(V2::new(0.3, 0.3), V2::new(1.0, 0.0), V2::new(0.0, 1.0), trilinear);
+        assert!((far - V3::new(0.5, 0.5, 0.5)).length() < 1e-9);
+
+        // edge on, one texel tall and 16 long: plain trilinear blurs to gray,
+        // anisotropic keeps the rows apart
+        let stripes = Texture::new(1, 2, &[0xFFFFFF, 0x000000]);
+        let (dx, dy) = (V2::new(8.0, 0.0), V2::new(0.0, 0.25));
+        let top = V2::new(0.5, 0.75);
+        let blurred = stripes.sample(top, dx, dy, trilinear);
+        assert!(blurred.x < 0.9);
+        let sharp = stripes.sample(top, dx, dy, Sampler {anisotropy: 16, ..trilinear});
+        assert!(sharp.x > blurred.x, "{:?} {:?}", sharp, blurred);
+    }
+
+    #[test]
+    fn ppm_and_png() {
+        let p3 = parse_ppm(b"P3\n# a comment\n2 1\n15\n15 0 0  0 0 15\n").unwrap();
+        assert_eq!((p3.width, p3.height), (2, 1));
+        assert_eq!(p3.texel(1, 0, Wrap::Clamp), V3::new(0.0, 0.0, 1.0));
+
+        let mut p6 = b"P6 2 1 255\n".to_vec();
+        p6.extend([255, 0, 0, 0, 128, 0]);
+        assert_eq!(parse_ppm(&p6).unwrap().texel(1, 0, Wrap::Clamp), color_to_v3(0x008000));
+        assert!(parse_ppm(b"P6 2 1 255\n\x00").unwrap_err().contains("cut short"));
+        assert!(parse_ppm(b"P5 2 1 255\n").unwrap_err().contains("not a ppm"));
+        // sizes that don't fit in memory are an error, not an overflow
+        assert!(parse_ppm(b"P6 4294967296 4294967296 255\n").unwrap_err().contains("too large"));
+        assert!(parse_ppm(b"P6 4000000000 1000000000 65535\n").unwrap_err().contains("too large"));
+        assert!(parse_ppm(b"P3 4294967296 4294967296 255\n").unwrap_err().contains("too large"));
+
+        let mut png_bytes = vec![];
+        let mut encoder = png::Encoder::new(&mut png_bytes, 2, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[255, 0, 0, 255, 0, 0, 255, 10]).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(parse_png(&png_bytes).unwrap().texel(1, 0, Wrap::Clamp), V3::new(0.0, 0.0, 1.0));
+    }
+}
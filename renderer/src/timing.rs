@@ -0,0 +1,249 @@
+// frame timing for the main loops: how long the last frame took, an optional
+// fps cap, a fixed step accumulator for simulations that want one, and a
+// once a second frame time report
+// spinning_cube pulls this file in too so it can't lean on anything in the crate
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const USAGE: &str = "    --fps <n>           cap the frame rate, 0 means as fast as possible (default 60)
+    --stats             print frame times once a second
+    --fixed-step <s>    move the animation on in steps this many seconds long and draw each
+                        frame part way between the last two";
+
+pub struct TimingOptions {
+    pub target_fps: Option<f64>,
+    pub stats: bool,
+    pub fixed_step: Option<f64>,
+}
+
+// takes the timing flags out of the args and leaves the rest for everyone else
+pub fn take_args(args: &mut Vec<String>) -> Result<TimingOptions, String> {
+    let mut options = TimingOptions {target_fps: Some(60.0), stats: false, fixed_step: None};
+
+    if let Some(i) = args.iter().position(|a| a == "--stats") {
+        args.remove(i);
+        options.stats = true;
+    }
+    if let Some(i) = args.iter().position(|a| a == "--fps") {
+        let value = args.get(i + 1).ok_or("--fps needs a value")?;
+        let fps: f64 = value.parse().map_err(|_| format!("bad fps: {}", value))?;
+        // NaN, infinite, or so small that a frame would last forever
+        if fps < 0.0 || !fps.is_finite() || (fps > 0.0 && !(1.0 / fps).is_finite()) {
+            return Err(format!("bad fps: {}", value));
+        }
+        options.target_fps = if fps == 0.0 {None} else {Some(fps)};
+        args.drain(i..i + 2);
+    }
+    if let Some(i) = args.iter().position(|a| a == "--fixed-step") {
+        let value = args.get(i + 1).ok_or("--fixed-step needs a value")?;
+        let step = value.parse().ok().filter(|s: &f64| *s > 0.0 && s.is_finite())
+            .ok_or_else(|| format!("bad fixed step: {}", value))?;
+        options.fixed_step = Some(step);
+        args.drain(i..i + 2);
+    }
+    Ok(options)
+}
+
+pub struct FrameClock {
+    start: Instant,
+    last: Instant,
+    target_fps: Option<f64>,
+    simulated: Option<f64>, // fixed dt, used when there is no real time to follow
+    pub dt: f64,   // seconds the last frame took
+    pub time: f64, // seconds since the clock started
+    stats: FrameStats,
+}
+
+impl FrameClock {
+    // follows the wall clock, sleeping if we're ahead of the fps cap
+    pub fn new(target_fps: Option<f64>) -> FrameClock {
+        let now = Instant::now();
+        FrameClock {start: now, last: now, target_fps, simulated: None, dt: 0.0, time: 0.0, stats: FrameStats::new()}
+    }
+
+    // every tick is exactly 1/fps long however long it really took,
+    // for headless renders that should come out the same on every machine
+    pub fn simulated(fps: f64) -> FrameClock {
+        let mut clock = FrameClock::new(None);
+        clock.simulated = Some(1.0 / fps);
+        clock
+    }
+
+    // call once per frame, after drawing, gives back the new dt
+    pub fn tick(&mut self) -> f64 {
+        if let Some(dt) = self.simulated {
+            self.stats.record(self.last.elapsed().as_secs_f64());
+            self.last = Instant::now();
+            self.dt = dt;
+            self.time += dt;
+            return dt;
+        }
+
+        // the time spent on the frame itself, before any sleeping
+        let work = self.last.elapsed().as_secs_f64();
+        self.stats.record(work);
+
+        if let Some(fps) = self.target_fps {
+            let frame = Duration::from_secs_f64(1.0 / fps);
+            let spent = self.last.elapsed();
+            if spent < frame {
+                thread::sleep(frame - spent);
+            }
+        }
+
+        let now = Instant::now();
+        self.dt = (now - self.last).as_secs_f64();
+        self.time = (now - self.start).as_secs_f64();
+        self.last = now;
+        self.dt
+    }
+
+    // Some(line) about once a second
+    pub fn report(&mut self) -> Option<String> {
+        self.stats.report()
+    }
+}
+
+// min/avg/max of how long frames took to render, reset after every report
+struct FrameStats {
+    since: Instant,
+    frames: usize,
+    total: f64,
+    min: f64,
+    max: f64,
+}
+
+impl FrameStats {
+    fn new() -> FrameStats {
+        FrameStats {since: Instant::now(), frames: 0, total: 0.0, min: f64::INFINITY, max: 0.0}
+    }
+
+    fn record(&mut self, seconds: f64) {
+        self.frames += 1;
+        self.total += seconds;
+        self.min = self.min.min(seconds);
+        self.max = self.max.max(seconds);
+    }
+
+    fn report(&mut self) -> Option<String> {
+        let window = self.since.elapsed().as_secs_f64();
+        if window < 1.0 || self.frames == 0 {
+            return None;
+        }
+        let line = format!("{:.1} fps | frame {:.2} ms avg, {:.2} min, {:.2} max",
+                           self.frames as f64 / window,
+                           self.total / self.frames as f64 * 1000.0,
+                           self.min * 1000.0,
+                           self.max * 1000.0);
+        *self = FrameStats::new();
+        Some(line)
+    }
+}
+
+// the usual accumulator: real time goes in, whole simulation steps come out,
+// and alpha says how far we are between the last step and the next one so
+// drawing can blend the two states
+pub struct FixedStep {
+    pub step: f64,
+    acc: f64,
+}
+
+// past this many steps in one frame we give up catching up, otherwise one
+// slow frame makes the next one slower and so on
+const MAX_STEPS: usize = 8;
+
+impl FixedStep {
+    pub fn new(step: f64) -> FixedStep {
+        assert!(step > 0.0 && step.is_finite(), "a fixed step of {} seconds never gets anywhere", step);
+        FixedStep {step, acc: 0.0}
+    }
+
+    // how many steps to run for a frame that took `dt` seconds, a clock
+    // going backwards (or NaN) counts as no time at all
+    pub fn advance(&mut self, dt: f64) -> usize {
+        if dt > 0.0 {
+            self.acc += dt;
+        }
+        // checked up front so a long stall doesn't spin through every step it missed
+        if self.acc >= self.step * (MAX_STEPS + 1) as f64 {
+            self.acc = 0.0;
+            return MAX_STEPS;
+        }
+        let mut steps = 0;
+        while self.acc >= self.step {
+            self.acc -= self.step;
+            steps += 1;
+        }
+        steps
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.acc / self.step
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn fps_flag() {
+        let mut rest = args("--fps 30 --stats --frames 2");
+        let options = take_args(&mut rest).unwrap();
+        assert_eq!((options.target_fps, options.stats), (Some(30.0), true));
+        assert_eq!(rest, args("--frames 2"));
+        assert_eq!(take_args(&mut args("--fps 0")).unwrap().target_fps, None);
+        for bad in ["-1", "NaN", "inf", "1e-320", "fast"] {
+            assert!(take_args(&mut args(&format!("--fps {}", bad))).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn fixed_step_flag() {
+        assert_eq!(take_args(&mut args("--stats")).unwrap().fixed_step, None);
+        let mut rest = args("--headless --fixed-step 0.05");
+        assert_eq!(take_args(&mut rest).unwrap().fixed_step, Some(0.05));
+        assert_eq!(rest, args("--headless"));
+        for bad in ["0", "-1", "NaN", "inf", "slow"] {
+            assert!(take_args(&mut args(&format!("--fixed-step {}", bad))).is_err(), "{}", bad);
+        }
+        assert!(take_args(&mut args("--fixed-step")).is_err());
+    }
+
+    #[test]
+    fn fixed_steps() {
+        let mut fixed = FixedStep::new(0.25);
+        assert_eq!(fixed.advance(0.625), 2);
+        assert_eq!(fixed.alpha(), 0.5);
+        assert_eq!(fixed.advance(0.125), 1);
+        assert_eq!(fixed.alpha(), 0.0);
+        assert_eq!(fixed.advance(-5.0), 0);
+        assert_eq!(fixed.advance(f64::NAN), 0);
+        // a long stall runs the most steps allowed and forgets the rest
+        assert_eq!(fixed.advance(1e12), MAX_STEPS);
+        assert_eq!(fixed.alpha(), 0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_step() {
+        FixedStep::new(0.0);
+    }
+
+    #[test]
+    fn simulated_clock() {
+        let mut clock = FrameClock::simulated(4.0);
+        assert_eq!(clock.time, 0.0);
+        for _ in 0..3 {
+            assert_eq!(clock.tick(), 0.25);
+        }
+        assert_eq!((clock.dt, clock.time), (0.25, 0.75));
+        // no report until a second of real time has gone by
+        assert!(clock.report().is_none());
+    }
+}
@@ -1,5 +1,17 @@
 use minifb::{Key, Window, WindowOptions};
 
+// the image writer lives with the renderer, no point keeping two copies
+#[path = "../../renderer/src/output.rs"]
+mod output;
+#[path = "../../renderer/src/timing.rs"]
+mod timing;
+// the cube only needs the rotation, which is all the base of the math is
+#[path = "../../renderer/src/math/base.rs"]
+mod math;
+
+use math::{Mat4, V3};
+use timing::{FixedStep, FrameClock};
+
 // https://github.com/saatvikrao/Spinning-Cube/blob/main/spinning_cube.c
 
 const WIDTH: usize = 800;
@@ -8,6 +20,12 @@ const FAR: f64 = 40.0;
 
 const CUBE_WIDTH: i32 = 20; 
 
+// the cube turns this much around each axis per second, and the spin is
+// simulated in steps of this size so it's the same on every machine
+const SPIN_SPEED: f64 = 0.6;
+const SIM_STEP: f64 = 1.0 / 60.0;
+const HEADLESS_FPS: f64 = 30.0;
+
 const RED:   u32 = 0xFF0000;
 const GREEN: u32 = 0x00FF00;
 const BLUE:  u32 = 0x0000FF;
@@ -15,11 +33,12 @@ const BLUE:  u32 = 0x0000FF;
 
 
 fn reset_screen() -> Vec<u32> {
-    return vec![0; WIDTH * HEIGHT];
+    vec![0; WIDTH * HEIGHT]
 }
 
-fn update_side(buf: &mut [u32], zbuf: &mut [f64], cX: f64, cY: f64, cZ: f64, ax: f64, ay: f64, az: f64, hof: f64, color: u32) {
-    let coords = get_coords(cX, cY, cZ, ax, ay, az); 
+#[allow(clippy::too_many_arguments)]
+fn update_side(buf: &mut [u32], zbuf: &mut [f64], c_x: f64, c_y: f64, c_z: f64, ax: f64, ay: f64, az: f64, hof: f64, color: u32) {
+    let coords = get_coords(c_x, c_y, c_z, ax, ay, az); 
     let x = coords.0; let y = coords.1; let z = coords.2 + FAR; 
     let ooz = 1.0 / z; 
 
@@ -29,60 +48,101 @@ fn update_side(buf: &mut [u32], zbuf: &mut [f64], cX: f64, cY: f64, cZ: f64, ax:
 
     let idx = xp + yp * (WIDTH as i32);
     
-    if idx >= 0 && idx < (WIDTH * HEIGHT) as i32 {
-        if ooz > zbuf[idx as usize] {
-            zbuf[idx as usize] = ooz;
-            buf[idx as usize] = color;
-        }
+    if idx >= 0 && idx < (WIDTH * HEIGHT) as i32 && ooz > zbuf[idx as usize] {
+        zbuf[idx as usize] = ooz;
+        buf[idx as usize] = color;
     }
 }
 
 
 fn get_coords(i: f64, j: f64, k: f64, ax: f64, ay: f64, az: f64) -> (f64, f64, f64) {
-    let (mut x, mut y, mut z) = (i, j * ax.cos() - k * ax.sin(), j * ax.sin() + k * ax.cos());
-    let (nx, ny, nz) = (x * ay.cos() + z * ay.sin(), y, -x * ay.sin() + z * ay.cos());
-    let (fx, fy, fz) = (nx * az.cos() - ny * az.sin(), nx * az.sin() + ny * az.cos(), nz);
-
-    (fx, fy, fz)
+    let v = Mat4::rotate(V3::new(ax, ay, az)).transform_point(V3::new(i, j, k));
+    (v.x, v.y, v.z)
 }
 
-fn main() {
-    let mut buffer: Vec<u32> = reset_screen(); 
+// draws the whole cube at the given angles, one point at a time like the C version
+fn render_cube(ax: f64, ay: f64, az: f64, hof: f64, is: f64) -> Vec<u32> {
+    let mut buffer = reset_screen(); 
+    let mut zbuf = vec![f64::NEG_INFINITY; WIDTH * HEIGHT]; 
 
-    let mut window = Window::new("Spinning Cube", WIDTH, HEIGHT, WindowOptions::default())
-        .unwrap_or_else(|e| panic!("Error making a window! You goofed!: {}", e));
+    let mut cx = -(CUBE_WIDTH as f64);
     
-    let mut ax = 0.0;
-    let mut ay = 0.0;
-    let mut az = 0.0;
+    while cx < CUBE_WIDTH as f64 {
+        let mut cy = -(CUBE_WIDTH as f64);
+        while cy < CUBE_WIDTH as f64 {
+            update_side(&mut buffer, &mut zbuf, cx, cy, -(CUBE_WIDTH as f64), ax, ay, az, hof, RED); 
+            update_side(&mut buffer, &mut zbuf, CUBE_WIDTH as f64, cy, cx , ax, ay, az, hof, GREEN); 
+            update_side(&mut buffer, &mut zbuf, -(CUBE_WIDTH as f64), cy, -cx, ax, ay, az, hof, GREEN); 
+            update_side(&mut buffer, &mut zbuf, -cx, cy, CUBE_WIDTH as f64, ax, ay, az, hof, RED); 
+
+            update_side(&mut buffer, &mut zbuf, cx, -(CUBE_WIDTH as f64), -cy, ax, ay, az, hof, BLUE); 
+            update_side(&mut buffer, &mut zbuf, cx, CUBE_WIDTH as f64, cy, ax, ay, az, hof, BLUE); 
+            cy += is; 
+        }
+        cx += is; 
+    } 
+    buffer
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let timing = timing::take_args(&mut args).unwrap_or_else(|e| {
+        eprintln!("{}\n{}\n{}", e, output::USAGE, timing::USAGE);
+        std::process::exit(2);
+    });
+    let headless = output::parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}\n{}\n{}", e, output::USAGE, timing::USAGE);
+        std::process::exit(2);
+    });
+
+    // the angle before and after the latest simulation step, the frame
+    // gets drawn somewhere in between depending on leftover time
+    let mut prev = 0.0;
+    let mut angle = 0.0;
+    let mut sim = FixedStep::new(timing.fixed_step.unwrap_or(SIM_STEP));
     let hof = -2.0 * (CUBE_WIDTH as f64); 
     let is = 0.4; 
 
-    while window.is_open() && !window.is_key_down(Key::Q) {
-        buffer = reset_screen(); 
-        let mut zbuf = vec![f64::NEG_INFINITY; WIDTH * HEIGHT]; 
-
-        let mut cx = -1.0 * CUBE_WIDTH as f64;
-        
-        while cx < CUBE_WIDTH as f64 {
-            let mut cy = -1.0 * CUBE_WIDTH as f64;
-            while cy < CUBE_WIDTH as f64 {
-                update_side(&mut buffer, &mut zbuf, cx, cy, -1.0 * CUBE_WIDTH as f64, ax, ay, az, hof, RED); 
-                update_side(&mut buffer, &mut zbuf, CUBE_WIDTH as f64, cy, cx , ax, ay, az, hof, GREEN); 
-                update_side(&mut buffer, &mut zbuf, -1.0 * CUBE_WIDTH as f64, cy, -1.0 * cx, ax, ay, az, hof, GREEN); 
-                update_side(&mut buffer, &mut zbuf, -1.0 * cx, cy, CUBE_WIDTH as f64, ax, ay, az, hof, RED); 
-
-                update_side(&mut buffer, &mut zbuf, cx, -1.0 * CUBE_WIDTH as f64, -1.0 * cy, ax, ay, az, hof, BLUE); 
-                update_side(&mut buffer, &mut zbuf, cx, CUBE_WIDTH as f64, cy, ax, ay, az, hof, BLUE); 
-                cy += is; 
-            }
-            cx += is; 
-        } 
-
-        ax += 0.01;
-        ay += 0.01;
-        az += 0.01; 
-
-        window.update_with_buffer(&buffer, WIDTH, HEIGHT).unwrap();
+    let mut clock = match headless {
+        Some(_) => FrameClock::simulated(HEADLESS_FPS),
+        None => FrameClock::new(timing.target_fps),
+    };
+
+    let mut window = match headless {
+        Some(_) => None,
+        None => Some(Window::new("Spinning Cube", WIDTH, HEIGHT, WindowOptions::default())
+            .unwrap_or_else(|e| panic!("Error making a window! You goofed!: {}", e))),
+    };
+
+    let mut frame = 0;
+    loop {
+        match (&window, &headless) {
+            (Some(w), _) if !w.is_open() || w.is_key_down(Key::Q) => break,
+            (None, Some(settings)) if frame >= settings.frames => break,
+            _ => {}
+        }
+
+        let a = prev + (angle - prev) * sim.alpha();
+        let buffer = render_cube(a, a, a, hof, is);
+
+        if let Some(w) = &mut window {
+            w.update_with_buffer(&buffer, WIDTH, HEIGHT).unwrap();
+        } else if let Some(settings) = &headless {
+            let path = output::write_frame(settings, frame, &buffer, WIDTH, HEIGHT).unwrap_or_else(|e| {
+                eprintln!("Couldn't write frame {}: {}", frame, e);
+                std::process::exit(1);
+            });
+            println!("wrote {}", path.display());
+        }
+        frame += 1;
+
+        let dt = clock.tick();
+        for _ in 0..sim.advance(dt) {
+            prev = angle;
+            angle += SPIN_SPEED * sim.step;
+        }
+        if let (true, Some(report)) = (timing.stats, clock.report()) {
+            println!("{}", report);
+        }
     }
-}
\ No newline at end of file
+}